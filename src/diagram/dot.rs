@@ -0,0 +1,84 @@
+use std::fmt::Write;
+
+use super::Diagram;
+
+/// Quotes a name or label as a DOT string
+fn quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
+impl Diagram {
+    /// Renders the diagram as a Graphviz DOT graph called `name`
+    pub fn to_dot(&self, name: &str) -> String {
+        let mut out = String::new();
+
+        // Writing to a String never fails
+        let _ = writeln!(out, "digraph {} {{", quote(name));
+        let _ = writeln!(out, "    rankdir = LR;");
+        let _ = writeln!(out, "    node [shape = circle];");
+        let _ = writeln!(out, "    __start [shape = point, label = \"\"];");
+
+        for (index, state) in self.states.iter().enumerate() {
+            let shape = if self.final_states.contains(&index) {
+                "doublecircle"
+            } else {
+                "circle"
+            };
+            let _ = writeln!(out, "    {} [shape = {shape}];", quote(state));
+        }
+
+        let _ = writeln!(out, "    __start -> {};", quote(&self.states[self.start]));
+
+        for edge in &self.edges {
+            let _ = writeln!(
+                out,
+                "    {} -> {} [label = {}];",
+                quote(&self.states[edge.from]),
+                quote(&self.states[edge.to]),
+                quote(&edge.label())
+            );
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::machine::symbols::SymbolTable;
+
+    #[test]
+    fn renders_start_finals_and_merged_edges() {
+        let mut table = SymbolTable::new();
+        let start = table.intern_state("{q0,q1}");
+        let dead = table.intern_state("∅");
+        let mut diagram = Diagram::new(&table, start, &HashSet::from([dead]));
+        diagram.add_edge(start, dead, "a".to_string());
+        diagram.add_edge(start, dead, "b".to_string());
+        diagram.add_edge(dead, dead, "a, \"A\" / POP".to_string());
+        diagram.add_edge(dead, dead, "b, B / POP".to_string());
+
+        assert_eq!(
+            diagram.to_dot("machine"),
+            r#"digraph "machine" {
+    rankdir = LR;
+    node [shape = circle];
+    __start [shape = point, label = ""];
+    "{q0,q1}" [shape = circle];
+    "∅" [shape = doublecircle];
+    __start -> "{q0,q1}";
+    "{q0,q1}" -> "∅" [label = "a, b"];
+    "∅" -> "∅" [label = "a, \"A\" / POP\nb, B / POP"];
+}
+"#
+        );
+    }
+}
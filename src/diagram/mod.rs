@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+
+use crate::machine::{
+    Configuration,
+    symbols::{StateId, SymbolTable},
+};
+
+mod dot;
+mod svg;
+
+/// Kind-agnostic view of a machine as a state diagram: every machine kind boils down to
+/// named states and labelled edges between them, which is all the renderers care about
+#[derive(Debug, Clone)]
+pub struct Diagram {
+    /// Name of every state, in declaration order
+    pub states: Vec<String>,
+    pub start: usize,
+    pub final_states: HashSet<usize>,
+    /// Edges in the order they were first added, one per pair of states
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    /// One label per transition between the two states
+    pub labels: Vec<String>,
+}
+
+impl Edge {
+    /// Labels merged into one. Labels that contain commas themselves (PDA and TM moves)
+    /// get a line each
+    pub fn label(&self) -> String {
+        if self.labels.iter().any(|label| label.contains(',')) {
+            self.labels.join("\n")
+        } else {
+            self.labels.join(", ")
+        }
+    }
+}
+
+/// States and edges a run went through, to highlight them on a rendered diagram
+#[derive(Debug, Clone, Default)]
+pub struct RunPath {
+    pub states: HashSet<usize>,
+    /// `(from, to)` pairs of state indices
+    pub edges: HashSet<(usize, usize)>,
+}
+
+impl Diagram {
+    /// Diagram with every state of `table` and no edges yet
+    pub fn new(table: &SymbolTable, start: StateId, final_states: &HashSet<StateId>) -> Self {
+        Self {
+            states: table
+                .states()
+                .map(|state| table.state_name(state).to_string())
+                .collect(),
+            start: start.index(),
+            final_states: final_states.iter().map(|state| state.index()).collect(),
+            edges: vec![],
+        }
+    }
+
+    /// Adds a transition, merging it into the existing edge between the same states
+    pub fn add_edge(&mut self, from: StateId, to: StateId, label: String) {
+        let (from, to) = (from.index(), to.index());
+
+        match self
+            .edges
+            .iter_mut()
+            .find(|edge| edge.from == from && edge.to == to)
+        {
+            Some(edge) => edge.labels.push(label),
+            None => self.edges.push(Edge {
+                from,
+                to,
+                labels: vec![label],
+            }),
+        }
+    }
+
+    /// Maps the configurations of a traced run onto the diagram. An NFA moves between
+    /// sets of states, so every edge linking two consecutive sets counts as taken
+    pub fn run_path(&self, trace: &[Configuration]) -> RunPath {
+        let indices = |configuration: &Configuration| -> Vec<usize> {
+            configuration
+                .states()
+                .into_iter()
+                .filter_map(|name| self.states.iter().position(|state| state == name))
+                .collect()
+        };
+
+        let mut path = RunPath::default();
+        let mut previous: Vec<usize> = vec![];
+
+        for configuration in trace {
+            let current = indices(configuration);
+
+            for &from in &previous {
+                for &to in &current {
+                    if self
+                        .edges
+                        .iter()
+                        .any(|edge| edge.from == from && edge.to == to)
+                    {
+                        path.edges.insert((from, to));
+                    }
+                }
+            }
+
+            path.states.extend(&current);
+            previous = current;
+        }
+
+        path
+    }
+}
@@ -0,0 +1,461 @@
+use std::{collections::VecDeque, fmt::Write};
+
+use super::{Diagram, RunPath};
+
+const MARGIN: f64 = 20.0;
+const FONT_SIZE: f64 = 14.0;
+const LINE_HEIGHT: f64 = 16.0;
+/// Rough width of a character, there is no font metrics to measure text with
+const CHAR_WIDTH: f64 = 8.0;
+const MIN_RADIUS: f64 = 22.0;
+const LAYER_GAP: f64 = 90.0;
+const ROW_GAP: f64 = 70.0;
+/// How far above its state a self loop reaches
+const LOOP_HEIGHT: f64 = 45.0;
+
+const STROKE: &str = "#222222";
+const HIGHLIGHT: &str = "#d62728";
+const HIGHLIGHT_FILL: &str = "#fde0dd";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn text_width(text: &str) -> f64 {
+    text.lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0) as f64
+        * CHAR_WIDTH
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+impl Point {
+    fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    /// Point `distance` away from `self`, in the direction of `towards`
+    fn towards(self, towards: Point, distance: f64) -> Point {
+        let (dx, dy) = (towards.x - self.x, towards.y - self.y);
+        let length = dx.hypot(dy).max(f64::EPSILON);
+        Point::new(
+            self.x + dx / length * distance,
+            self.y + dy / length * distance,
+        )
+    }
+}
+
+/// Area covered by everything drawn so far, to size the picture
+struct Bounds {
+    min: Point,
+    max: Point,
+}
+
+impl Bounds {
+    fn new() -> Self {
+        Self {
+            min: Point::new(f64::INFINITY, f64::INFINITY),
+            max: Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    fn include(&mut self, point: Point, half_width: f64, half_height: f64) {
+        self.min.x = self.min.x.min(point.x - half_width);
+        self.min.y = self.min.y.min(point.y - half_height);
+        self.max.x = self.max.x.max(point.x + half_width);
+        self.max.y = self.max.y.max(point.y + half_height);
+    }
+}
+
+/// Position of every state: states are put in layers by their distance from the start
+/// state, left to right, and ordered inside each layer to keep edges short
+struct Layout {
+    layers: Vec<usize>,
+    centers: Vec<Point>,
+    radii: Vec<f64>,
+}
+
+impl Layout {
+    fn new(diagram: &Diagram) -> Self {
+        let count = diagram.states.len();
+        let radii: Vec<f64> = diagram
+            .states
+            .iter()
+            .map(|state| (text_width(state) / 2.0 + 10.0).max(MIN_RADIUS))
+            .collect();
+
+        // Breadth first from the start state, unreachable states go in a layer of their own
+        let mut layers = vec![usize::MAX; count];
+        let mut queue = VecDeque::from([diagram.start]);
+        layers[diagram.start] = 0;
+        while let Some(state) = queue.pop_front() {
+            for edge in diagram.edges.iter().filter(|edge| edge.from == state) {
+                if layers[edge.to] == usize::MAX {
+                    layers[edge.to] = layers[state] + 1;
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+
+        let reachable_depth = layers.iter().filter(|&&layer| layer != usize::MAX).max();
+        let unreachable_layer = reachable_depth.map_or(0, |depth| depth + 1);
+        for layer in &mut layers {
+            if *layer == usize::MAX {
+                *layer = unreachable_layer;
+            }
+        }
+
+        let layer_count = layers.iter().max().map_or(0, |depth| depth + 1);
+        let mut rows: Vec<Vec<usize>> = vec![vec![]; layer_count];
+        for (state, &layer) in layers.iter().enumerate() {
+            rows[layer].push(state);
+        }
+
+        // Order each layer by the average row of the states leading into it
+        for layer in 1..layer_count {
+            let (before, after) = rows.split_at_mut(layer);
+            let previous = &before[layer - 1];
+            let barycenter = |state: usize| {
+                let rows: Vec<usize> = diagram
+                    .edges
+                    .iter()
+                    .filter(|edge| edge.to == state)
+                    .filter_map(|edge| previous.iter().position(|&other| other == edge.from))
+                    .collect();
+                if rows.is_empty() {
+                    f64::MAX
+                } else {
+                    rows.iter().sum::<usize>() as f64 / rows.len() as f64
+                }
+            };
+
+            after[0].sort_by(|&a, &b| barycenter(a).total_cmp(&barycenter(b)));
+        }
+
+        // Leave room between layers for the widest label
+        let widest_label = diagram
+            .edges
+            .iter()
+            .map(|edge| text_width(&edge.label()))
+            .fold(0.0, f64::max);
+        let layer_gap = LAYER_GAP.max(widest_label + 30.0);
+        let tallest_label = diagram
+            .edges
+            .iter()
+            .map(|edge| edge.labels.len())
+            .max()
+            .unwrap_or(1) as f64;
+        let row_gap = ROW_GAP + LOOP_HEIGHT + tallest_label * LINE_HEIGHT;
+        let max_radius = radii.iter().copied().fold(MIN_RADIUS, f64::max);
+
+        let tallest_layer = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let row_step = 2.0 * max_radius + row_gap;
+        let mut centers = vec![Point::new(0.0, 0.0); count];
+        let mut x = 0.0;
+        for row in &rows {
+            let width = row.iter().map(|&state| radii[state]).fold(0.0, f64::max);
+            x += width;
+
+            // Center shorter layers next to the tallest one
+            let offset = (tallest_layer - row.len()) as f64 * row_step / 2.0;
+            for (index, &state) in row.iter().enumerate() {
+                centers[state] = Point::new(x, offset + index as f64 * row_step);
+            }
+
+            x += width + layer_gap;
+        }
+
+        Self {
+            layers,
+            centers,
+            radii,
+        }
+    }
+}
+
+/// Multi-line text centered on `center`
+fn text(out: &mut String, center: Point, text: &str, color: &str) {
+    let lines: Vec<&str> = text.lines().collect();
+    let first_baseline =
+        center.y - (lines.len() as f64 - 1.0) * LINE_HEIGHT / 2.0 + FONT_SIZE / 3.0;
+
+    let _ = write!(
+        out,
+        r#"<text x="{:.1}" y="{:.1}" fill="{color}" text-anchor="middle">"#,
+        center.x, first_baseline
+    );
+    for (index, line) in lines.iter().enumerate() {
+        let dy = if index == 0 { 0.0 } else { LINE_HEIGHT };
+        let _ = write!(
+            out,
+            r#"<tspan x="{:.1}" dy="{dy}">{}</tspan>"#,
+            center.x,
+            escape(line)
+        );
+    }
+    out.push_str("</text>\n");
+}
+
+impl Diagram {
+    /// Lays out the diagram and renders it as a standalone SVG picture. States and edges
+    /// on `path` are drawn highlighted
+    pub fn to_svg(&self, path: Option<&RunPath>) -> String {
+        let layout = Layout::new(self);
+        let mut bounds = Bounds::new();
+        let mut body = String::new();
+
+        let on_path =
+            |from: usize, to: usize| path.is_some_and(|path| path.edges.contains(&(from, to)));
+
+        for edge in &self.edges {
+            let color = if on_path(edge.from, edge.to) {
+                HIGHLIGHT
+            } else {
+                STROKE
+            };
+            let marker = if color == HIGHLIGHT {
+                "arrow-highlight"
+            } else {
+                "arrow"
+            };
+            let width = if color == HIGHLIGHT { 2.5 } else { 1.2 };
+            let label = edge.label();
+            let label_height = edge.labels.len() as f64 * LINE_HEIGHT;
+            let center = layout.centers[edge.from];
+            let radius = layout.radii[edge.from];
+
+            if edge.from == edge.to {
+                // Self loop drawn as a curl above the state
+                let start = Point::new(center.x - radius * 0.5, center.y - radius * 0.87);
+                let end = Point::new(center.x + radius * 0.5, center.y - radius * 0.87);
+                let top = center.y - radius - LOOP_HEIGHT;
+                let _ = writeln!(
+                    body,
+                    r#"<path d="M {:.1} {:.1} C {:.1} {:.1}, {:.1} {:.1}, {:.1} {:.1}" fill="none" stroke="{color}" stroke-width="{width}" marker-end="url(#{marker})"/>"#,
+                    start.x,
+                    start.y,
+                    center.x - radius * 1.2,
+                    top,
+                    center.x + radius * 1.2,
+                    top,
+                    end.x,
+                    end.y
+                );
+
+                // The curve peaks about three quarters of the way up
+                let label_center = Point::new(
+                    center.x,
+                    center.y - radius - LOOP_HEIGHT * 0.75 - 4.0 - label_height / 2.0,
+                );
+                text(&mut body, label_center, &label, color);
+                bounds.include(label_center, text_width(&label) / 2.0, label_height / 2.0);
+                continue;
+            }
+
+            let from = center;
+            let to = layout.centers[edge.to];
+            let (dx, dy) = (to.x - from.x, to.y - from.y);
+            let length = dx.hypot(dy).max(f64::EPSILON);
+            // Left hand side of the edge, so edges going both ways bend apart
+            let normal = Point::new(dy / length, -dx / length);
+
+            let has_reverse = self
+                .edges
+                .iter()
+                .any(|other| other.from == edge.to && other.to == edge.from);
+            let adjacent_layers = layout.layers[edge.to] == layout.layers[edge.from] + 1;
+            let bend = if has_reverse || !adjacent_layers {
+                (length * 0.2).clamp(25.0, 80.0)
+            } else {
+                0.0
+            };
+
+            let control = Point::new(
+                (from.x + to.x) / 2.0 + normal.x * bend,
+                (from.y + to.y) / 2.0 + normal.y * bend,
+            );
+            let start = from.towards(control, radius);
+            let end = to.towards(control, layout.radii[edge.to]);
+            let _ = writeln!(
+                body,
+                r#"<path d="M {:.1} {:.1} Q {:.1} {:.1}, {:.1} {:.1}" fill="none" stroke="{color}" stroke-width="{width}" marker-end="url(#{marker})"/>"#,
+                start.x, start.y, control.x, control.y, end.x, end.y
+            );
+            bounds.include(control, 0.0, 0.0);
+
+            // Label just outside the middle of the curve
+            let middle = Point::new(
+                0.25 * start.x + 0.5 * control.x + 0.25 * end.x,
+                0.25 * start.y + 0.5 * control.y + 0.25 * end.y,
+            );
+            let clearance =
+                6.0 + (normal.x.abs() * text_width(&label) + normal.y.abs() * label_height) / 2.0;
+            let label_center = Point::new(
+                middle.x + normal.x * clearance,
+                middle.y + normal.y * clearance,
+            );
+            text(&mut body, label_center, &label, color);
+            bounds.include(label_center, text_width(&label) / 2.0, label_height / 2.0);
+        }
+
+        for (state, name) in self.states.iter().enumerate() {
+            let center = layout.centers[state];
+            let radius = layout.radii[state];
+            let highlighted = path.is_some_and(|path| path.states.contains(&state));
+            let (color, fill, width) = if highlighted {
+                (HIGHLIGHT, HIGHLIGHT_FILL, 2.5)
+            } else {
+                (STROKE, "white", 1.2)
+            };
+
+            let _ = writeln!(
+                body,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="{radius:.1}" fill="{fill}" stroke="{color}" stroke-width="{width}"/>"#,
+                center.x, center.y
+            );
+            if self.final_states.contains(&state) {
+                let _ = writeln!(
+                    body,
+                    r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}" fill="none" stroke="{color}" stroke-width="{width}"/>"#,
+                    center.x,
+                    center.y,
+                    radius - 4.0
+                );
+            }
+            text(&mut body, center, name, color);
+            bounds.include(center, radius, radius);
+
+            if state == self.start {
+                let tail = Point::new(center.x - radius - 30.0, center.y);
+                let _ = writeln!(
+                    body,
+                    r#"<path d="M {:.1} {:.1} L {:.1} {:.1}" stroke="{STROKE}" stroke-width="1.2" marker-end="url(#arrow)"/>"#,
+                    tail.x,
+                    tail.y,
+                    center.x - radius,
+                    center.y
+                );
+                bounds.include(tail, 0.0, 0.0);
+            }
+        }
+
+        // Self loops and their labels stick out above the states
+        for (state, center) in layout.centers.iter().enumerate() {
+            if self
+                .edges
+                .iter()
+                .any(|edge| edge.from == state && edge.to == state)
+            {
+                bounds.include(*center, 0.0, layout.radii[state] + LOOP_HEIGHT);
+            }
+        }
+
+        let (x, y) = (bounds.min.x - MARGIN, bounds.min.y - MARGIN);
+        let (width, height) = (
+            bounds.max.x - bounds.min.x + 2.0 * MARGIN,
+            bounds.max.y - bounds.min.y + 2.0 * MARGIN,
+        );
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{x:.1} {y:.1} {width:.1} {height:.1}" width="{width:.0}" height="{height:.0}" font-family="sans-serif" font-size="{FONT_SIZE}">"#
+        );
+        out.push_str("<defs>\n");
+        for (id, color) in [("arrow", STROKE), ("arrow-highlight", HIGHLIGHT)] {
+            let _ = writeln!(
+                out,
+                r#"<marker id="{id}" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="8" markerHeight="8" markerUnits="userSpaceOnUse" orient="auto"><path d="M 0 0 L 10 5 L 0 10 z" fill="{color}"/></marker>"#
+            );
+        }
+        out.push_str("</defs>\n");
+        let _ = writeln!(
+            out,
+            r#"<rect x="{x:.1}" y="{y:.1}" width="{width:.1}" height="{height:.1}" fill="white"/>"#
+        );
+        out.push_str(&body);
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::machine::symbols::SymbolTable;
+
+    /// Checks every tag is closed in order, and returns the text found between tags
+    fn well_formed(svg: &str) -> Vec<&str> {
+        let mut open: Vec<&str> = vec![];
+        let mut texts = vec![];
+        let mut rest = svg;
+        while let Some(start) = rest.find('<') {
+            let text = rest[..start].trim();
+            assert!(!text.contains(['>', '"']), "{text:?}");
+            if !text.is_empty() {
+                texts.push(text);
+            }
+
+            let end = start + rest[start..].find('>').expect("unclosed tag");
+            let tag = &rest[start + 1..end];
+            let name = tag
+                .trim_start_matches('/')
+                .split_whitespace()
+                .next()
+                .unwrap();
+            if let Some(closed) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(closed));
+            } else if !tag.ends_with('/') {
+                open.push(name);
+            }
+            rest = &rest[end + 1..];
+        }
+
+        assert!(rest.trim().is_empty());
+        assert!(open.is_empty(), "{open:?}");
+        texts
+    }
+
+    #[test]
+    fn draws_every_state_and_labelled_edge() {
+        let mut table = SymbolTable::new();
+        let states = ["q0", "q1", "<q2>"].map(|name| table.intern_state(name));
+        let mut diagram = Diagram::new(&table, states[0], &HashSet::from([states[2]]));
+        diagram.add_edge(states[0], states[1], "a".to_string());
+        diagram.add_edge(states[1], states[0], "b".to_string());
+        diagram.add_edge(states[1], states[2], "c & d".to_string());
+        diagram.add_edge(states[2], states[2], "e, X / POP".to_string());
+        diagram.add_edge(states[2], states[2], "f, Y / POP".to_string());
+
+        let svg = diagram.to_svg(None);
+        let texts = well_formed(&svg);
+        for text in [
+            "q0",
+            "q1",
+            "&lt;q2&gt;",
+            "a",
+            "b",
+            "c &amp; d",
+            "e, X / POP",
+            "f, Y / POP",
+        ] {
+            assert!(texts.contains(&text), "{text:?} missing");
+        }
+
+        // A circle per state, and a second one around the final state
+        assert_eq!(svg.matches("<circle").count(), 3 + 1);
+        // A path per edge, and one for the start arrow
+        assert_eq!(svg.matches("marker-end").count(), diagram.edges.len() + 1);
+    }
+}
@@ -0,0 +1,36 @@
+//! Parsing and simulation of finite automata, pushdown automata and Turing machines
+//! described in the fla-lab text format.
+//!
+//! ```
+//! use fla::machine::{Machine, MachineKind};
+//! use fla::parser::Parser;
+//!
+//! let src = "
+//! [states]
+//! q0, q1
+//! [alphabet]
+//! 0, 1
+//! [initial]
+//! q0
+//! [final]
+//! q1
+//! [transitions]
+//! q0(0) => (q0)
+//! q0(1) => (q1)
+//! q1(0) => (q1)
+//! q1(1) => (q0)
+//! ";
+//!
+//! let tokens = Parser::lex(src)?;
+//! let parsed = Parser::parse(src, tokens)?;
+//! let mut machine = MachineKind::Dfa.build(parsed, src)?;
+//!
+//! assert!(machine.run("01").accepted());
+//! assert!(!machine.run("11").accepted());
+//! # Ok::<(), miette::Report>(())
+//! ```
+
+pub mod diagram;
+pub mod lint;
+pub mod machine;
+pub mod parser;
@@ -0,0 +1,363 @@
+//! Warnings about machine files that are valid but most likely not what their author
+//! meant, like states nothing leads to or symbols no transition uses.
+
+use std::collections::{HashMap, HashSet};
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::machine::{pda::Acceptance, reachable};
+use crate::parser::{PartialMachineInfo, StackTransition, Token};
+
+#[derive(Error, Diagnostic, Debug, Clone)]
+pub enum Lint {
+    #[error("State {name} can't be reached from the initial state")]
+    #[diagnostic(severity(Warning))]
+    UnreachableState {
+        name: String,
+        #[label("declared here")]
+        at: SourceSpan,
+    },
+    #[error("State {name} can never lead to a final state")]
+    #[diagnostic(
+        severity(Warning),
+        help("every input reaching it is rejected, which only a trap state should do")
+    )]
+    DeadState {
+        name: String,
+        #[label("declared here")]
+        at: SourceSpan,
+    },
+    #[error("Final state {name} has no incoming transition")]
+    #[diagnostic(severity(Warning))]
+    FinalStateWithoutIncoming {
+        name: String,
+        #[label("declared final here")]
+        at: SourceSpan,
+    },
+    #[error("Symbol {name} is never read by a transition")]
+    #[diagnostic(severity(Warning))]
+    UnusedSymbol {
+        name: String,
+        #[label("declared here")]
+        at: SourceSpan,
+    },
+    #[error("Stack symbol {name} is never pushed")]
+    #[diagnostic(severity(Warning))]
+    UnpushedStackSymbol {
+        name: String,
+        #[label("declared here")]
+        at: SourceSpan,
+    },
+    #[error("Tape symbol {name} is never written")]
+    #[diagnostic(
+        severity(Warning),
+        help("it isn't an input symbol or the blank either, so it can never be on the tape")
+    )]
+    UnwrittenTapeSymbol {
+        name: String,
+        #[label("declared here")]
+        at: SourceSpan,
+    },
+}
+
+/// Looks for likely mistakes in a parsed machine file. Names are compared as written,
+/// so this works the same for every kind of machine and never fails: unknown names are
+/// left for `Info::new` to report. `acceptance` is the mode a PDA runs under, which may
+/// not be the one declared in the file
+pub fn lint(machine: &PartialMachineInfo, src: &str, acceptance: Acceptance) -> Vec<Lint> {
+    let mut lints = vec![];
+
+    let mut forward: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut backward: HashMap<&str, Vec<&str>> = HashMap::new();
+    for transition in &machine.transitions {
+        let (from, to) = (transition.from.initial.src(src), transition.to.0.src(src));
+        forward.entry(from).or_default().push(to);
+        backward.entry(to).or_default().push(from);
+    }
+
+    let start = machine.start_state.src(src);
+    let finals: Vec<&str> = machine
+        .final_states
+        .iter()
+        .map(|state| state.src(src))
+        .collect();
+    let from_start = reachable([start], |state| {
+        forward.get(state).into_iter().flatten().copied()
+    });
+    let to_final = reachable(finals, |state| {
+        backward.get(state).into_iter().flatten().copied()
+    });
+
+    // A PDA accepting by empty stack alone may accept in any state
+    let any_state_accepts = acceptance == Acceptance::EmptyStack;
+
+    // Reported on their own, as they are unreachable for a more specific reason
+    let mut without_incoming = HashSet::new();
+    for token in &machine.final_states {
+        let name = token.src(src);
+        if name != start && !backward.contains_key(name) && without_incoming.insert(name) {
+            lints.push(Lint::FinalStateWithoutIncoming {
+                name: name.to_string(),
+                at: token.span(),
+            });
+        }
+    }
+
+    for token in &machine.states {
+        let name = token.src(src);
+        if without_incoming.contains(name) {
+            continue;
+        }
+
+        if !from_start.contains(name) {
+            lints.push(Lint::UnreachableState {
+                name: name.to_string(),
+                at: token.span(),
+            });
+        } else if !to_final.contains(name) && !any_state_accepts {
+            let is_trap = forward
+                .get(name)
+                .is_none_or(|targets| targets.iter().all(|&target| target == name));
+            if !is_trap {
+                lints.push(Lint::DeadState {
+                    name: name.to_string(),
+                    at: token.span(),
+                });
+            }
+        }
+    }
+
+    let read: HashSet<&str> = machine
+        .transitions
+        .iter()
+        .map(|transition| transition.from.with_symbol.src(src))
+        .collect();
+    for token in &machine.alphabet {
+        if !read.contains(token.src(src)) {
+            lints.push(Lint::UnusedSymbol {
+                name: token.src(src).to_string(),
+                at: token.span(),
+            });
+        }
+    }
+
+    let written = |matches: fn(&StackTransition) -> &[Token]| -> HashSet<&str> {
+        machine
+            .transitions
+            .iter()
+            .filter_map(|transition| transition.to.1.as_ref())
+            .flat_map(matches)
+            .map(|token| token.src(src))
+            .collect()
+    };
+
+    if let Some(stack_alphabet) = &machine.stack_alphabet {
+        let mut pushed = written(|operation| match operation {
+            StackTransition::Push(_, symbols) | StackTransition::Replace(_, symbols) => symbols,
+            _ => &[],
+        });
+        pushed.extend(machine.start_stack.iter().map(|token| token.src(src)));
+
+        for token in stack_alphabet {
+            if !pushed.contains(token.src(src)) {
+                lints.push(Lint::UnpushedStackSymbol {
+                    name: token.src(src).to_string(),
+                    at: token.span(),
+                });
+            }
+        }
+    }
+
+    if let Some(tape_alphabet) = &machine.tape_alphabet {
+        let mut on_tape = written(|operation| match operation {
+            StackTransition::Write(_, symbol) => std::slice::from_ref(symbol),
+            _ => &[],
+        });
+        on_tape.extend(machine.alphabet.iter().map(|token| token.src(src)));
+        on_tape.extend(machine.blank_symbol.iter().map(|token| token.src(src)));
+
+        for token in tape_alphabet {
+            if !on_tape.contains(token.src(src)) {
+                lints.push(Lint::UnwrittenTapeSymbol {
+                    name: token.src(src).to_string(),
+                    at: token.span(),
+                });
+            }
+        }
+    }
+
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::parse;
+
+    /// Messages of the lints found in a machine accepting by final state
+    fn messages(src: &str) -> Vec<String> {
+        lint(&parse(src), src, Acceptance::FinalState)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn reports_unreachable_states() {
+        let src = "
+[states]
+q0, q1, q2
+[alphabet]
+a
+[initial]
+q0
+[final]
+q1
+[transitions]
+q0(a) => (q1)
+q1(a) => (q1)
+q2(a) => (q1)
+";
+        assert_eq!(
+            messages(src),
+            ["State q2 can't be reached from the initial state"]
+        );
+    }
+
+    #[test]
+    fn reports_dead_states_but_not_traps() {
+        let src = "
+[states]
+q0, q1, dead, trap
+[alphabet]
+a, b
+[initial]
+q0
+[final]
+q1
+[transitions]
+q0(a) => (q1)
+q0(b) => (dead)
+q1(a) => (q1)
+q1(b) => (trap)
+dead(a) => (trap)
+dead(b) => (trap)
+trap(a) => (trap)
+trap(b) => (trap)
+";
+        assert_eq!(
+            messages(src),
+            ["State dead can never lead to a final state"]
+        );
+    }
+
+    #[test]
+    fn reports_final_states_without_incoming_transitions_alone() {
+        let src = "
+[states]
+q0, q1, f
+[alphabet]
+a
+[initial]
+q0
+[final]
+q1, f
+[transitions]
+q0(a) => (q1)
+q1(a) => (q1)
+f(a) => (q1)
+";
+        assert_eq!(messages(src), ["Final state f has no incoming transition"]);
+    }
+
+    #[test]
+    fn reports_unused_symbols() {
+        let src = "
+[states]
+q0
+[alphabet]
+a, b
+[initial]
+q0
+[final]
+q0
+[transitions]
+q0(a) => (q0)
+";
+        assert_eq!(messages(src), ["Symbol b is never read by a transition"]);
+    }
+
+    #[test]
+    fn reports_stack_symbols_never_pushed() {
+        let src = "
+[states]
+q0, q1
+[alphabet]
+a
+[stack_alphabet]
+Z, A, B
+[initial]
+q0
+[start_stack]
+Z
+[final]
+q1
+[transitions]
+q0(a) => (q0, PUSH:A)
+q0(ε, Z) => (q1, NOOP)
+";
+        assert_eq!(messages(src), ["Stack symbol B is never pushed"]);
+    }
+
+    #[test]
+    fn reports_tape_symbols_never_written() {
+        let src = "
+[states]
+q0, q1
+[alphabet]
+1
+[tape_alphabet]
+1, x, y, _
+[blank_symbol]
+_
+[initial]
+q0
+[final]
+q1
+[transitions]
+q0(1) => (q0, WRITE:x, RIGHT)
+q0(_) => (q1, WRITE:_, LEFT)
+";
+        assert_eq!(messages(src), ["Tape symbol y is never written"]);
+    }
+
+    #[test]
+    fn states_are_not_dead_when_the_empty_stack_accepts() {
+        let src = "
+[states]
+q0, q1
+[alphabet]
+a, b
+[stack_alphabet]
+A
+[initial]
+q0
+[final]
+[transitions]
+q0(a) => (q0, PUSH:A)
+q0(b, A) => (q1, POP)
+q1(b, A) => (q1, POP)
+";
+        let parsed = parse(src);
+        let dead_states = |acceptance| {
+            lint(&parsed, src, acceptance)
+                .iter()
+                .filter(|lint| matches!(lint, Lint::DeadState { .. }))
+                .count()
+        };
+
+        assert_eq!(dead_states(Acceptance::FinalState), 1);
+        assert_eq!(dead_states(Acceptance::EmptyStack), 0);
+    }
+}
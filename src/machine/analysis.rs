@@ -0,0 +1,235 @@
+use std::collections::{HashMap, HashSet, VecDeque, hash_map::Entry};
+
+use serde::Serialize;
+
+use super::{
+    dfa, reachable,
+    symbols::{StateId, SymbolId},
+};
+
+/// Properties of the language of a finite automaton
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Analysis {
+    /// No string is accepted
+    pub empty: bool,
+    /// Finitely many strings are accepted
+    pub finite: bool,
+    /// Every string over the alphabet is accepted
+    pub universal: bool,
+    /// Shortest accepted string, the first one in alphabet order among those
+    pub shortest_accepted: Option<String>,
+    /// Shortest rejected string, the first one in alphabet order among those
+    pub shortest_rejected: Option<String>,
+    /// Number of accepted strings of each length, indexed by the length. Counts too big
+    /// for a `u128` are capped
+    pub accepted_by_length: Vec<u128>,
+}
+
+/// Shortest string leading from the initial state to a state satisfying `goal`, found
+/// breadth first trying symbols in declaration order
+fn shortest(
+    info: &dfa::Info,
+    alphabet: &[SymbolId],
+    goal: impl Fn(StateId) -> bool,
+) -> Option<String> {
+    let mut parents: HashMap<StateId, Option<(StateId, SymbolId)>> =
+        HashMap::from([(info.start_state, None)]);
+    let mut queue = VecDeque::from([info.start_state]);
+
+    while let Some(state) = queue.pop_front() {
+        if goal(state) {
+            let mut symbols = vec![];
+            let mut current = state;
+            while let Some((parent, symbol)) = parents[&current] {
+                symbols.push(info.table.symbol_name(symbol));
+                current = parent;
+            }
+            symbols.reverse();
+            return Some(symbols.concat());
+        }
+
+        for &symbol in alphabet {
+            let target = info.next(state, symbol);
+            if let Entry::Vacant(entry) = parents.entry(target) {
+                entry.insert(Some((state, symbol)));
+                queue.push_back(target);
+            }
+        }
+    }
+
+    None
+}
+
+/// States both reachable from the initial state and able to reach a final state, the
+/// only ones an accepted string goes through
+fn useful_states(info: &dfa::Info, alphabet: &[SymbolId]) -> HashSet<StateId> {
+    let from_start = reachable([info.start_state], |state| {
+        alphabet.iter().map(move |&symbol| info.next(state, symbol))
+    });
+
+    let mut incoming: HashMap<StateId, Vec<StateId>> = HashMap::new();
+    for &state in &from_start {
+        for &symbol in alphabet {
+            incoming
+                .entry(info.next(state, symbol))
+                .or_default()
+                .push(state);
+        }
+    }
+
+    let finals = info
+        .final_states
+        .iter()
+        .copied()
+        .filter(|state| from_start.contains(state));
+    reachable(finals, |state| {
+        incoming.get(&state).into_iter().flatten().copied()
+    })
+}
+
+/// Whether the moves between `states` go around a cycle
+fn has_cycle(info: &dfa::Info, alphabet: &[SymbolId], states: &HashSet<StateId>) -> bool {
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Mark {
+        Visiting,
+        Done,
+    }
+
+    let mut marks: HashMap<StateId, Mark> = HashMap::new();
+    for &root in states {
+        if marks.contains_key(&root) {
+            continue;
+        }
+
+        // Depth first, each entry being a state and the index of its next symbol to try
+        marks.insert(root, Mark::Visiting);
+        let mut stack = vec![(root, 0)];
+        while let Some((state, index)) = stack.pop() {
+            let Some(&symbol) = alphabet.get(index) else {
+                marks.insert(state, Mark::Done);
+                continue;
+            };
+            stack.push((state, index + 1));
+
+            let target = info.next(state, symbol);
+            if !states.contains(&target) {
+                continue;
+            }
+            match marks.get(&target) {
+                Some(Mark::Visiting) => return true,
+                Some(Mark::Done) => {}
+                None => {
+                    marks.insert(target, Mark::Visiting);
+                    stack.push((target, 0));
+                }
+            }
+        }
+    }
+
+    false
+}
+
+/// Analyzes the language of a DFA, counting accepted strings up to `max_length`
+pub fn analyze(info: &dfa::Info, max_length: usize) -> Analysis {
+    let mut alphabet: Vec<SymbolId> = info.alphabet.iter().copied().collect();
+    alphabet.sort_unstable();
+
+    let shortest_accepted = shortest(info, &alphabet, |state| info.final_states.contains(&state));
+    let shortest_rejected = shortest(info, &alphabet, |state| !info.final_states.contains(&state));
+    let useful = useful_states(info, &alphabet);
+
+    // Number of strings of the current length leading to each state
+    let mut counts = HashMap::from([(info.start_state, 1u128)]);
+    let mut accepted_by_length = vec![];
+    for length in 0..=max_length {
+        let accepted = counts
+            .iter()
+            .filter(|(state, _)| info.final_states.contains(state))
+            .fold(0u128, |total, (_, &count)| total.saturating_add(count));
+        accepted_by_length.push(accepted);
+
+        if length < max_length {
+            let mut next_counts: HashMap<StateId, u128> = HashMap::new();
+            for (&state, &count) in &counts {
+                for &symbol in &alphabet {
+                    let total = next_counts.entry(info.next(state, symbol)).or_default();
+                    *total = total.saturating_add(count);
+                }
+            }
+            counts = next_counts;
+        }
+    }
+
+    Analysis {
+        empty: shortest_accepted.is_none(),
+        finite: !has_cycle(info, &alphabet, &useful),
+        universal: shortest_rejected.is_none(),
+        shortest_accepted,
+        shortest_rejected,
+        accepted_by_length,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{parse, regex::Regex, subset};
+
+    fn analyze_pattern(pattern: &str, max_length: usize) -> Analysis {
+        let alphabet = ["a", "b"].map(String::from);
+        let nfa = Regex::parse(pattern).unwrap().to_nfa(Some(&alphabet));
+        analyze(&subset::determinize(&nfa), max_length)
+    }
+
+    #[test]
+    fn infinite_languages_count_their_strings() {
+        let analysis = analyze_pattern("(a|b)*abb", 6);
+
+        assert!(!analysis.empty && !analysis.finite && !analysis.universal);
+        assert_eq!(analysis.shortest_accepted.as_deref(), Some("abb"));
+        assert_eq!(analysis.shortest_rejected.as_deref(), Some(""));
+        assert_eq!(analysis.accepted_by_length, [0, 0, 0, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn finite_languages_have_no_useful_cycle() {
+        let analysis = analyze_pattern("ba|a|ab", 4);
+
+        assert!(!analysis.empty && analysis.finite && !analysis.universal);
+        assert_eq!(analysis.shortest_accepted.as_deref(), Some("a"));
+        assert_eq!(analysis.accepted_by_length, [0, 1, 2, 0, 0]);
+    }
+
+    #[test]
+    fn nothing_accepted_is_empty_and_finite() {
+        let src = "
+[states]
+q0
+[alphabet]
+a, b
+[initial]
+q0
+[final]
+[transitions]
+q0(a) => (q0)
+q0(b) => (q0)
+";
+        let info = dfa::Info::new(parse(src), src).unwrap();
+        let analysis = analyze(&info, 3);
+
+        assert!(analysis.empty && analysis.finite && !analysis.universal);
+        assert_eq!(analysis.shortest_accepted, None);
+        assert_eq!(analysis.shortest_rejected.as_deref(), Some(""));
+        assert_eq!(analysis.accepted_by_length, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn everything_accepted_is_universal() {
+        let analysis = analyze_pattern("(a|b)*", 3);
+
+        assert!(!analysis.empty && !analysis.finite && analysis.universal);
+        assert_eq!(analysis.shortest_accepted.as_deref(), Some(""));
+        assert_eq!(analysis.shortest_rejected, None);
+        assert_eq!(analysis.accepted_by_length, [1, 2, 4, 8]);
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    TransitionFrom, TransitionTo, nfa,
+    symbols::{StateId, SymbolId, SymbolTable},
+};
+
+/// Assembles a new NFA out of copies of existing ones, glued together with epsilon moves
+struct Builder {
+    table: SymbolTable,
+    alphabet: HashSet<SymbolId>,
+    transitions: HashMap<TransitionFrom, Vec<TransitionTo>>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            table: SymbolTable::new(),
+            alphabet: HashSet::from([SymbolId::EPSILON]),
+            transitions: HashMap::new(),
+        }
+    }
+
+    /// Copies every state and transition of `info`, with `prefix` put in front of the
+    /// state names. Returns the new id of each state, indexed by its old one
+    fn copy(&mut self, info: &nfa::Info, prefix: &str) -> Vec<StateId> {
+        let states = self.copy_states(info, prefix);
+        self.copy_transitions(info, &states, false);
+        states
+    }
+
+    /// Copies the states and the alphabet of `info`, but none of its transitions
+    fn copy_states(&mut self, info: &nfa::Info, prefix: &str) -> Vec<StateId> {
+        let states = info
+            .table
+            .states()
+            .map(|state| {
+                let name = format!("{prefix}{}", info.table.state_name(state));
+                self.table.fresh_state(&name)
+            })
+            .collect();
+
+        let mut symbols: Vec<SymbolId> = info.alphabet.iter().copied().collect();
+        symbols.sort_unstable();
+        for symbol in symbols {
+            let name = info.table.symbol_name(symbol);
+            self.alphabet.insert(self.table.intern_symbol(name));
+        }
+
+        states
+    }
+
+    /// Copies the transitions of `info` between the states returned by `copy_states`,
+    /// turning each of them around when `reversed` is set
+    fn copy_transitions(&mut self, info: &nfa::Info, states: &[StateId], reversed: bool) {
+        for (from, targets) in &info.transitions {
+            let with_symbol = self
+                .table
+                .symbol(info.table.symbol_name(from.with_symbol))
+                .expect("alphabet covers every symbol");
+            for to in targets {
+                let (from, to) = (states[from.initial.index()], states[to.0.index()]);
+                if reversed {
+                    self.add(to, with_symbol, from);
+                } else {
+                    self.add(from, with_symbol, to);
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, from: StateId, with_symbol: SymbolId, to: StateId) {
+        let targets = self
+            .transitions
+            .entry(TransitionFrom {
+                initial: from,
+                with_symbol,
+            })
+            .or_default();
+        if !targets.iter().any(|target| target.0 == to) {
+            targets.push(TransitionTo(to));
+        }
+    }
+
+    fn epsilon(&mut self, from: StateId, to: StateId) {
+        self.add(from, SymbolId::EPSILON, to);
+    }
+
+    fn finish(self, start_state: StateId, final_states: HashSet<StateId>) -> nfa::Info {
+        nfa::Info {
+            table: self.table,
+            alphabet: self.alphabet,
+            transitions: self.transitions,
+            start_state,
+            final_states,
+        }
+    }
+}
+
+/// Final states of `info`, under their new ids
+fn finals(info: &nfa::Info, states: &[StateId]) -> Vec<StateId> {
+    let mut finals: Vec<StateId> = info
+        .final_states
+        .iter()
+        .map(|state| states[state.index()])
+        .collect();
+    finals.sort_unstable();
+    finals
+}
+
+/// NFA accepting the strings either NFA accepts: a new initial state `start` with an
+/// epsilon move to each of them. States are prefixed with `a_` and `b_`
+pub fn union(first: &nfa::Info, second: &nfa::Info) -> nfa::Info {
+    let mut builder = Builder::new();
+    let start = builder.table.fresh_state("start");
+    let a = builder.copy(first, "a_");
+    let b = builder.copy(second, "b_");
+
+    builder.epsilon(start, a[first.start_state.index()]);
+    builder.epsilon(start, b[second.start_state.index()]);
+
+    let final_states = finals(first, &a).into_iter().chain(finals(second, &b));
+    builder.finish(start, final_states.collect())
+}
+
+/// NFA accepting a string of the first NFA followed by a string of the second one: the
+/// final states of the first NFA move on epsilon to the initial state of the second.
+/// States are prefixed with `a_` and `b_`
+pub fn concat(first: &nfa::Info, second: &nfa::Info) -> nfa::Info {
+    let mut builder = Builder::new();
+    let a = builder.copy(first, "a_");
+    let b = builder.copy(second, "b_");
+
+    for state in finals(first, &a) {
+        builder.epsilon(state, b[second.start_state.index()]);
+    }
+
+    let final_states = finals(second, &b).into_iter().collect();
+    builder.finish(a[first.start_state.index()], final_states)
+}
+
+/// NFA accepting any number of strings of the NFA, none included: a new final initial
+/// state `start` moves on epsilon to the old one, and so does every final state
+pub fn star(info: &nfa::Info) -> nfa::Info {
+    let mut builder = Builder::new();
+    let start = builder.table.fresh_state("start");
+    let states = builder.copy(info, "");
+    let old_start = states[info.start_state.index()];
+
+    builder.epsilon(start, old_start);
+    let mut final_states = HashSet::from([start]);
+    for state in finals(info, &states) {
+        builder.epsilon(state, old_start);
+        final_states.insert(state);
+    }
+
+    builder.finish(start, final_states)
+}
+
+/// NFA accepting the strings of the NFA spelled backwards: every transition is turned
+/// around, a new initial state `start` moves on epsilon to each old final state, and
+/// the old initial state becomes the only final one
+pub fn reverse(info: &nfa::Info) -> nfa::Info {
+    let mut builder = Builder::new();
+    let start = builder.table.fresh_state("start");
+    let states = builder.copy_states(info, "");
+    builder.copy_transitions(info, &states, true);
+
+    for state in finals(info, &states) {
+        builder.epsilon(start, state);
+    }
+
+    let final_states = HashSet::from([states[info.start_state.index()]]);
+    builder.finish(start, final_states)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{Machine as _, parse, regex::Regex, strings_up_to, subset};
+
+    fn nfa(pattern: &str) -> nfa::Info {
+        let alphabet = ["a", "b"].map(String::from);
+        Regex::parse(pattern).unwrap().to_nfa(Some(&alphabet))
+    }
+
+    /// Checks the NFA accepts exactly the strings over `ab` satisfying `expected`, and
+    /// that it reads back from the file it is written as
+    fn check(info: nfa::Info, expected: impl Fn(&str) -> bool) {
+        let written = info.to_string();
+        nfa::Info::new(parse(&written), &written).unwrap();
+
+        let mut machine = nfa::Machine::new(info);
+        for input in strings_up_to("ab", 6) {
+            assert_eq!(
+                machine.run(&input).accepted(),
+                expected(&input),
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn union_accepts_either_language() {
+        check(union(&nfa("a*"), &nfa("b+")), |input| {
+            !input.contains('b') || !input.contains('a')
+        });
+    }
+
+    #[test]
+    fn concat_accepts_one_string_after_the_other() {
+        check(concat(&nfa("a+"), &nfa("b+")), |input| {
+            let split = input.find('b').unwrap_or(input.len());
+            split > 0 && split < input.len() && !input[split..].contains('a')
+        });
+    }
+
+    #[test]
+    fn star_accepts_any_number_of_strings() {
+        check(star(&nfa("ab")), |input| {
+            input.len() % 2 == 0 && input.as_bytes().chunks(2).all(|pair| pair == b"ab")
+        });
+    }
+
+    #[test]
+    fn reverse_spells_the_strings_backwards() {
+        check(reverse(&nfa("a(a|b)*")), |input| input.ends_with('a'));
+    }
+
+    #[test]
+    fn determinized_machines_combine_into_readable_files() {
+        let dfa = subset::determinize(&nfa("(a|b)*b")).to_string();
+        let dfa = nfa::Info::new(parse(&dfa), &dfa).unwrap();
+
+        let union = union(&dfa, &nfa("a"));
+        assert!(union.to_string().contains("a_{"));
+        check(union, |input| input == "a" || input.ends_with('b'));
+    }
+}
@@ -1,24 +1,31 @@
 use miette::Diagnostic;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 use thiserror::Error;
 
+use crate::diagram::Diagram;
 use crate::parser::{ParserError, PartialMachineInfo, StackTransition};
 
-use super::{TransitionFrom, TransitionTo};
+use super::{
+    Configuration, Rejection, Step, TransitionFrom, TransitionTo, Verdict,
+    symbols::{StateId, SymbolId, SymbolTable},
+};
 
 #[derive(Error, Diagnostic, Debug)]
 pub enum DFAError {
     #[error("Cannot have multiple transitions from state '{initial}' with symbol '{with_symbol}'")]
     MultipleTransitions {
-        initial: &'static str,
-        with_symbol: &'static str,
+        initial: String,
+        with_symbol: String,
     },
     #[error(
         "DFA is incomplete: no transition defined for state '{initial}' with symbol '{with_symbol}'"
     )]
     IncompleteDFA {
-        initial: &'static str,
-        with_symbol: &'static str,
+        initial: String,
+        with_symbol: String,
     },
     #[error("DFA cannot have stack operations")]
     StackOperationsNotAllowed,
@@ -26,18 +33,17 @@ pub enum DFAError {
     TapeOperationsNotAllowed,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Info {
-    // We actually never need the full states hashset
-    // pub states: HashSet<&'static str>,
-    pub alphabet: HashSet<&'static str>,
+    pub table: SymbolTable,
+    pub alphabet: HashSet<SymbolId>,
     pub transitions: HashMap<TransitionFrom, TransitionTo>,
-    pub start_state: &'static str,
-    pub final_states: HashSet<&'static str>,
+    pub start_state: StateId,
+    pub final_states: HashSet<StateId>,
 }
 
 impl Info {
-    pub fn new(machine: PartialMachineInfo, src: &'static str) -> miette::Result<Self> {
+    pub fn new(machine: PartialMachineInfo, src: &str) -> miette::Result<Self> {
         if machine.stack_alphabet.is_some() || machine.start_stack.is_some() {
             return Err(DFAError::StackOperationsNotAllowed.into());
         }
@@ -46,39 +52,37 @@ impl Info {
             return Err(DFAError::TapeOperationsNotAllowed.into());
         }
 
+        let mut table = SymbolTable::new();
         let mut states = HashSet::new();
         let mut alphabet = HashSet::new();
         let mut transitions = HashMap::new();
         let mut final_states = HashSet::new();
 
         for state in machine.states {
-            states.insert(state.src(src));
+            states.insert(table.intern_state(state.src(src)));
         }
 
         for symbol in machine.alphabet {
-            alphabet.insert(symbol.src(src));
+            alphabet.insert(table.intern_symbol(symbol.src(src)));
         }
 
         for final_state in machine.final_states {
-            let state_str = final_state.src(src);
-
-            if !states.contains(state_str) {
+            let Some(state) = table.state(final_state.src(src)) else {
                 return Err(ParserError::UnknownState {
                     at: final_state.span(),
                 }
                 .into());
-            }
+            };
 
-            final_states.insert(state_str);
+            final_states.insert(state);
         }
 
-        let start_state_str = machine.start_state.src(src);
-        if !states.contains(start_state_str) {
+        let Some(start_state) = table.state(machine.start_state.src(src)) else {
             return Err(ParserError::UnknownState {
                 at: machine.start_state.span(),
             }
             .into());
-        }
+        };
 
         for transition in machine.transitions {
             // Check for stack operations
@@ -90,6 +94,7 @@ impl Info {
             if let Some(ref stack_trans) = transition.to.1 {
                 match stack_trans {
                     StackTransition::Push(_, _)
+                    | StackTransition::Replace(_, _)
                     | StackTransition::Pop(_)
                     | StackTransition::NoOp(_) => {
                         return Err(DFAError::StackOperationsNotAllowed.into());
@@ -105,31 +110,30 @@ impl Info {
                 return Err(DFAError::TapeOperationsNotAllowed.into());
             }
 
-            let from_state = transition.from.initial.src(src);
-            let symbol = transition.from.with_symbol.src(src);
-            let to_state = transition.to.0.src(src);
-
             // Validate transition states and symbols
-            if !states.contains(from_state) {
+            let Some(from_state) = table.state(transition.from.initial.src(src)) else {
                 return Err(ParserError::UnknownState {
                     at: transition.from.initial.span(),
                 }
                 .into());
-            }
+            };
 
-            if !states.contains(to_state) {
+            let Some(to_state) = table.state(transition.to.0.src(src)) else {
                 return Err(ParserError::UnknownState {
                     at: transition.to.0.span(),
                 }
                 .into());
-            }
+            };
 
-            if !alphabet.contains(symbol) {
+            let Some(symbol) = table
+                .symbol(transition.from.with_symbol.src(src))
+                .filter(|symbol| alphabet.contains(symbol))
+            else {
                 return Err(ParserError::UnknownAlphabetSymbol {
                     at: transition.from.with_symbol.span(),
                 }
                 .into());
-            }
+            };
 
             let key = TransitionFrom {
                 initial: from_state,
@@ -139,8 +143,8 @@ impl Info {
             // Check if there's already a transition for this state and symbol (violates DFA property)
             if transitions.contains_key(&key) {
                 return Err(DFAError::MultipleTransitions {
-                    initial: from_state,
-                    with_symbol: symbol,
+                    initial: table.state_name(from_state).to_string(),
+                    with_symbol: table.symbol_name(symbol).to_string(),
                 }
                 .into());
             }
@@ -149,8 +153,8 @@ impl Info {
         }
 
         // Check if the DFA is complete (each state has a transition for each symbol in the alphabet)
-        for state in &states {
-            for symbol in &alphabet {
+        for &state in &states {
+            for &symbol in &alphabet {
                 let key = TransitionFrom {
                     initial: state,
                     with_symbol: symbol,
@@ -158,8 +162,8 @@ impl Info {
 
                 if !transitions.contains_key(&key) {
                     return Err(DFAError::IncompleteDFA {
-                        initial: state,
-                        with_symbol: symbol,
+                        initial: table.state_name(state).to_string(),
+                        with_symbol: table.symbol_name(symbol).to_string(),
                     }
                     .into());
                 }
@@ -167,18 +171,92 @@ impl Info {
         }
 
         Ok(Info {
+            table,
             alphabet,
             transitions,
-            start_state: start_state_str,
+            start_state,
             final_states,
         })
     }
+
+    /// State the DFA moves to from `state` on `symbol`. The DFA is complete, so every
+    /// state has a move on every symbol of the alphabet
+    pub fn next(&self, state: StateId, symbol: SymbolId) -> StateId {
+        let key = TransitionFrom {
+            initial: state,
+            with_symbol: symbol,
+        };
+        self.transitions[&key].0
+    }
+
+    /// State diagram of the DFA, one edge label per input symbol
+    pub fn diagram(&self) -> Diagram {
+        let mut diagram = Diagram::new(&self.table, self.start_state, &self.final_states);
+
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by_key(|(from, _)| (from.initial, from.with_symbol));
+
+        for (from, to) in transitions {
+            let label = self.table.symbol_name(from.with_symbol).to_string();
+            diagram.add_edge(from.initial, to.0, label);
+        }
+
+        diagram
+    }
 }
 
-#[derive(Debug)]
+/// Writes the DFA back out in the text format it is parsed from
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = &self.table;
+
+        let states: Vec<&str> = table
+            .states()
+            .map(|state| table.state_name(state))
+            .collect();
+        writeln!(f, "[states]\n{}\n", states.join(", "))?;
+
+        let mut alphabet: Vec<SymbolId> = self.alphabet.iter().copied().collect();
+        alphabet.sort_unstable();
+        let symbols: Vec<&str> = alphabet
+            .iter()
+            .map(|&symbol| table.symbol_name(symbol))
+            .collect();
+        writeln!(f, "[alphabet]\n{}\n", symbols.join(", "))?;
+
+        writeln!(f, "[initial]\n{}\n", table.state_name(self.start_state))?;
+
+        let mut final_states: Vec<StateId> = self.final_states.iter().copied().collect();
+        final_states.sort_unstable();
+        let final_states: Vec<&str> = final_states
+            .iter()
+            .map(|&state| table.state_name(state))
+            .collect();
+        writeln!(f, "[final]\n{}\n", final_states.join(", "))?;
+
+        writeln!(f, "[transitions]")?;
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by_key(|(from, _)| (from.initial, from.with_symbol));
+        for (from, to) in transitions {
+            writeln!(
+                f,
+                "{}({}) => ({})",
+                table.state_name(from.initial),
+                table.symbol_name(from.with_symbol),
+                table.state_name(to.0)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Machine {
     info: Info,
-    current_state: &'static str,
+    current_state: StateId,
+    input: Vec<char>,
+    position: usize,
 }
 
 impl Machine {
@@ -186,41 +264,73 @@ impl Machine {
         Self {
             current_state: info.start_state,
             info,
+            input: vec![],
+            position: 0,
         }
     }
+}
 
-    pub fn run(mut self, input: &str) -> bool {
-        // Process each character in the input
-        for c in input.chars() {
-            // Find the matching symbol in the alphabet
-            let symbol = self
-                .info
-                .alphabet
-                .iter()
-                .find(|&&s| s.len() == 1 && s.chars().next().unwrap() == c);
-
-            if let Some(&symbol) = symbol {
-                let key = TransitionFrom {
-                    initial: self.current_state,
-                    with_symbol: symbol,
-                };
+impl super::Machine for Machine {
+    fn build(machine: PartialMachineInfo, src: &str) -> miette::Result<Self> {
+        Ok(Self::new(Info::new(machine, src)?))
+    }
 
-                if let Some(transition) = self.info.transitions.get(&key) {
-                    self.current_state = transition.0;
-                } else {
-                    println!(
-                        "No transition found for state '{}' with symbol '{}'. Counting as not accepted",
-                        self.current_state, symbol
-                    );
-                    return false;
-                }
+    fn clone_box(&self) -> Box<dyn super::Machine> {
+        Box::new(self.clone())
+    }
+
+    fn reset(&mut self, input: &str) {
+        self.current_state = self.info.start_state;
+        self.input = input.chars().collect();
+        self.position = 0;
+    }
+
+    fn step(&mut self) -> Step {
+        // Once the input is consumed, the verdict only depends on where we ended up
+        let Some(&c) = self.input.get(self.position) else {
+            return if self.info.final_states.contains(&self.current_state) {
+                Step::Halt(Verdict::Accepted)
             } else {
-                // Symbol not in alphabet
-                println!("Symbol '{}' not in alphabet. Counting as not accepted", c);
-                return false;
+                Step::Halt(Verdict::Rejected(Rejection::NotAccepting {
+                    state: self.info.table.state_name(self.current_state).to_string(),
+                }))
+            };
+        };
+
+        // Find the matching symbol in the alphabet
+        let symbol = self
+            .info
+            .table
+            .char_symbol(c)
+            .filter(|symbol| self.info.alphabet.contains(symbol));
+
+        if let Some(symbol) = symbol {
+            let key = TransitionFrom {
+                initial: self.current_state,
+                with_symbol: symbol,
+            };
+
+            if let Some(transition) = self.info.transitions.get(&key) {
+                self.current_state = transition.0;
+                self.position += 1;
+                Step::Continue
+            } else {
+                Step::Halt(Verdict::Rejected(Rejection::NoTransition {
+                    state: self.info.table.state_name(self.current_state).to_string(),
+                    symbol: self.info.table.symbol_name(symbol).to_string(),
+                    stack_top: None,
+                }))
             }
+        } else {
+            // Symbol not in alphabet
+            Step::Halt(Verdict::Rejected(Rejection::UnknownSymbol { symbol: c }))
         }
+    }
 
-        self.info.final_states.contains(self.current_state)
+    fn configuration(&self) -> Configuration {
+        Configuration::Dfa {
+            state: self.info.table.state_name(self.current_state).to_string(),
+            remaining_input: self.input[self.position..].iter().collect(),
+        }
     }
 }
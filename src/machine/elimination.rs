@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use super::{
+    nfa,
+    regex::Regex,
+    symbols::{StateId, SymbolId},
+};
+
+/// Node of the generalized NFA: the states of the machine plus a fresh initial and a
+/// fresh final state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    Start,
+    State(StateId),
+    End,
+}
+
+/// Generalized NFA, whose edges are labelled with regular expressions. A missing edge
+/// stands for `∅`
+struct Gnfa {
+    edges: HashMap<(Node, Node), Regex>,
+}
+
+impl Gnfa {
+    fn add(&mut self, from: Node, to: Node, regex: Regex) {
+        let edge = self.edges.remove(&(from, to)).unwrap_or(Regex::Empty);
+        let regex = Regex::union(edge, regex);
+        if regex != Regex::Empty {
+            self.edges.insert((from, to), regex);
+        }
+    }
+
+    fn incoming(&self, node: Node) -> Vec<Node> {
+        let mut nodes: Vec<Node> = self
+            .edges
+            .keys()
+            .filter(|&&(from, to)| to == node && from != node)
+            .map(|&(from, _)| from)
+            .collect();
+        nodes.sort_unstable_by_key(order);
+        nodes
+    }
+
+    fn outgoing(&self, node: Node) -> Vec<Node> {
+        let mut nodes: Vec<Node> = self
+            .edges
+            .keys()
+            .filter(|&&(from, to)| from == node && to != node)
+            .map(|&(_, to)| to)
+            .collect();
+        nodes.sort_unstable_by_key(order);
+        nodes
+    }
+
+    /// Removes `node`, rerouting every path through it: `in → node → out` becomes
+    /// `in → out` labelled `R(in, node) R(node, node)* R(node, out)`
+    fn eliminate(&mut self, node: Node) {
+        let incoming = self.incoming(node);
+        let outgoing = self.outgoing(node);
+        let loop_regex = Regex::star(self.edges.remove(&(node, node)).unwrap_or(Regex::Empty));
+
+        for &from in &incoming {
+            for &to in &outgoing {
+                let regex = Regex::concat(
+                    Regex::concat(self.edges[&(from, node)].clone(), loop_regex.clone()),
+                    self.edges[&(node, to)].clone(),
+                );
+                self.add(from, to, regex);
+            }
+        }
+
+        self.edges
+            .retain(|&(from, to), _| from != node && to != node);
+    }
+}
+
+/// Sort key keeping nodes in declaration order, so the output is stable
+fn order(node: &Node) -> (u8, Option<StateId>) {
+    match *node {
+        Node::Start => (0, None),
+        Node::State(state) => (1, Some(state)),
+        Node::End => (2, None),
+    }
+}
+
+/// Regular expression accepting the same language as the NFA, by state elimination.
+/// States are eliminated cheapest first, the ones with the fewest paths through them,
+/// which keeps the expression short
+pub fn to_regex(info: &nfa::Info) -> Regex {
+    let mut gnfa = Gnfa {
+        edges: HashMap::new(),
+    };
+
+    gnfa.add(Node::Start, Node::State(info.start_state), Regex::Epsilon);
+    for &state in &info.final_states {
+        gnfa.add(Node::State(state), Node::End, Regex::Epsilon);
+    }
+
+    let mut transitions: Vec<_> = info.transitions.iter().collect();
+    transitions.sort_by_key(|(from, _)| (from.initial, from.with_symbol));
+    for (from, targets) in transitions {
+        let regex = match from.with_symbol {
+            SymbolId::EPSILON => Regex::Epsilon,
+            symbol => Regex::Symbol(info.table.symbol_name(symbol).to_string()),
+        };
+
+        for to in targets {
+            gnfa.add(Node::State(from.initial), Node::State(to.0), regex.clone());
+        }
+    }
+
+    let mut remaining: Vec<StateId> = info.table.states().collect();
+    while !remaining.is_empty() {
+        let (index, _) = remaining
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, &state)| {
+                let node = Node::State(state);
+                gnfa.incoming(node).len() * gnfa.outgoing(node).len()
+            })
+            .expect("remaining is not empty");
+
+        gnfa.eliminate(Node::State(remaining.remove(index)));
+    }
+
+    gnfa.edges
+        .remove(&(Node::Start, Node::End))
+        .unwrap_or(Regex::Empty)
+}
@@ -0,0 +1,281 @@
+use serde::Serialize;
+
+use std::collections::HashSet;
+
+use super::{
+    Machine, Step, Verdict, dfa,
+    symbols::{StateId, SymbolId},
+};
+
+/// Accepted strings of a machine, in shortlex order: shorter strings first, strings of
+/// the same length in alphabet order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Enumeration {
+    pub accepted: Vec<String>,
+    /// Strings searched on the way for which the machine didn't halt within the step
+    /// budget, any of them may be accepted as well
+    pub undecided: Vec<String>,
+}
+
+/// Lists the first `limit` strings of at most `max_length` symbols a DFA accepts
+pub fn finite_automaton(info: &dfa::Info, limit: usize, max_length: usize) -> Enumeration {
+    let mut alphabet: Vec<SymbolId> = info.alphabet.iter().copied().collect();
+    alphabet.sort_unstable();
+
+    Enumeration {
+        accepted: accepted_strings(info, &alphabet, max_length)
+            .take(limit)
+            .collect(),
+        undecided: vec![],
+    }
+}
+
+/// Every string of at most `max_length` symbols a DFA accepts, in shortlex order. Each
+/// length is searched depth first, keeping only the path to the current string, and a
+/// move is only followed when a final state can be reached in exactly the number of
+/// symbols left, so every path the search takes ends in an accepted string
+fn accepted_strings<'a>(
+    info: &'a dfa::Info,
+    alphabet: &'a [SymbolId],
+    max_length: usize,
+) -> impl Iterator<Item = String> + 'a {
+    // States from which a final state is exactly as many moves away as the index
+    let mut finishing: Vec<HashSet<StateId>> = vec![info.final_states.clone()];
+    for moves in 1..=max_length {
+        let states = info
+            .table
+            .states()
+            .filter(|&state| {
+                alphabet
+                    .iter()
+                    .any(|&symbol| finishing[moves - 1].contains(&info.next(state, symbol)))
+            })
+            .collect();
+        finishing.push(states);
+    }
+
+    let mut length = 0;
+    // States along the path to the current string, each with the next symbol to try
+    let mut path: Vec<(StateId, usize)> = vec![];
+    let mut symbols: Vec<SymbolId> = vec![];
+    std::iter::from_fn(move || {
+        loop {
+            if path.is_empty() {
+                if length > max_length {
+                    return None;
+                }
+                if !finishing[length].contains(&info.start_state) {
+                    length += 1;
+                    continue;
+                }
+                path.push((info.start_state, 0));
+            }
+
+            let depth = path.len() - 1;
+            let (state, next) = path.last_mut().expect("the path is never empty here");
+            let string = if depth == length {
+                Some(
+                    symbols
+                        .iter()
+                        .map(|&symbol| info.table.symbol_name(symbol))
+                        .collect(),
+                )
+            } else if *next < alphabet.len() {
+                let symbol = alphabet[*next];
+                *next += 1;
+                let state = info.next(*state, symbol);
+                if finishing[length - depth - 1].contains(&state) {
+                    path.push((state, 0));
+                    symbols.push(symbol);
+                }
+                continue;
+            } else {
+                None
+            };
+
+            // Done with this state, back to the one before it
+            path.pop();
+            symbols.pop();
+            if path.is_empty() {
+                length += 1;
+            }
+            if string.is_some() {
+                return string;
+            }
+        }
+    })
+}
+
+/// Every string over `alphabet` of at most `max_length` symbols, in shortlex order
+fn shortlex(alphabet: &[String], max_length: usize) -> impl Iterator<Item = String> + '_ {
+    // Digits of the current string in base `alphabet.len()`, most significant first
+    let mut digits: Option<Vec<usize>> = Some(vec![]);
+    std::iter::from_fn(move || {
+        let current = digits.take()?;
+        let string = current
+            .iter()
+            .map(|&digit| alphabet[digit].as_str())
+            .collect();
+
+        let mut next = current;
+        let mut position = next.len();
+        loop {
+            if position == 0 {
+                // Every string of this length was listed, move on to the next length
+                if next.len() < max_length && !alphabet.is_empty() {
+                    digits = Some(vec![0; next.len() + 1]);
+                }
+                break;
+            }
+            position -= 1;
+            next[position] += 1;
+            if next[position] < alphabet.len() {
+                digits = Some(next);
+                break;
+            }
+            next[position] = 0;
+        }
+
+        Some(string)
+    })
+}
+
+/// What became of the run on a candidate string
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Decision {
+    Accepted,
+    Rejected,
+    OutOfSteps,
+}
+
+/// Lists the first `limit` strings of at most `max_length` symbols a machine accepts, for
+/// machines that may never halt. Runs are dovetailed: each round starts the machine on
+/// the next candidate string and advances every run still going by one step, so a run
+/// that loops forever can't hold the others back. Steps are counted like
+/// [`Machine::run`] does, and a run is given up when it would take more than `max_steps`.
+/// `machine` is only copied when every copy made so far is still running
+pub fn dovetail(
+    alphabet: &[String],
+    limit: usize,
+    max_length: usize,
+    max_steps: usize,
+    machine: &dyn Machine,
+) -> Enumeration {
+    let mut candidates = shortlex(alphabet, max_length);
+    let mut strings: Vec<String> = vec![];
+    let mut decisions: Vec<Option<Decision>> = vec![];
+    // Runs still going, along with the index of their candidate and their step count
+    let mut running: Vec<(usize, Box<dyn Machine>, usize)> = vec![];
+    // Copies whose run is over, ready for another candidate
+    let mut idle: Vec<Box<dyn Machine>> = vec![];
+    let mut exhausted = false;
+
+    let mut enumeration = Enumeration::default();
+    // First candidate whose decision hasn't been reported yet
+    let mut cursor = 0;
+
+    loop {
+        if !exhausted {
+            match candidates.next() {
+                Some(string) => {
+                    let mut copy = idle.pop().unwrap_or_else(|| machine.clone_box());
+                    copy.reset(&string);
+                    running.push((strings.len(), copy, 0));
+                    strings.push(string);
+                    decisions.push(None);
+                }
+                None => exhausted = true,
+            }
+        }
+
+        let mut still_running = vec![];
+        for (index, mut machine, steps) in running.drain(..) {
+            let decision = match machine.step() {
+                Step::Halt(Verdict::Accepted) => Decision::Accepted,
+                Step::Halt(Verdict::Rejected(_)) => Decision::Rejected,
+                Step::Continue if steps < max_steps => {
+                    still_running.push((index, machine, steps + 1));
+                    continue;
+                }
+                Step::Continue => Decision::OutOfSteps,
+            };
+
+            decisions[index] = Some(decision);
+            idle.push(machine);
+        }
+        running = still_running;
+
+        // Report candidates in order, as soon as every one before them is decided
+        while let Some(&Some(decision)) = decisions.get(cursor) {
+            match decision {
+                Decision::Accepted => {
+                    if enumeration.accepted.len() == limit {
+                        return enumeration;
+                    }
+                    enumeration.accepted.push(strings[cursor].clone());
+                }
+                Decision::Rejected => {}
+                Decision::OutOfSteps => enumeration.undecided.push(strings[cursor].clone()),
+            }
+            cursor += 1;
+        }
+
+        if enumeration.accepted.len() == limit || (exhausted && running.is_empty()) {
+            return enumeration;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{regex::Regex, strings_up_to, subset};
+
+    fn ends_with_abb() -> dfa::Info {
+        subset::determinize(&Regex::parse("(a|b)*abb").unwrap().to_nfa(None))
+    }
+
+    #[test]
+    fn lists_accepted_strings_in_shortlex_order() {
+        let expected: Vec<String> = strings_up_to("ab", 7)
+            .into_iter()
+            .filter(|string| string.ends_with("abb"))
+            .collect();
+
+        let enumeration = finite_automaton(&ends_with_abb(), 1000, 7);
+        assert_eq!(enumeration.accepted, expected);
+        assert!(enumeration.undecided.is_empty());
+
+        let enumeration = finite_automaton(&ends_with_abb(), 5, 7);
+        assert_eq!(enumeration.accepted, expected[..5]);
+    }
+
+    #[test]
+    fn dovetailing_agrees_with_the_search() {
+        let alphabet = ["a", "b"].map(String::from);
+        let machine = dfa::Machine::new(ends_with_abb());
+
+        assert_eq!(
+            dovetail(&alphabet, 10, 6, 100, &machine),
+            finite_automaton(&ends_with_abb(), 10, 6)
+        );
+    }
+
+    #[test]
+    fn runs_halting_within_the_step_budget_are_decided() {
+        let alphabet = ["a", "b"].map(String::from);
+        let mut machine = dfa::Machine::new(ends_with_abb());
+        let most_steps = strings_up_to("ab", 4)
+            .iter()
+            .map(|input| machine.run(input).steps)
+            .max()
+            .unwrap();
+
+        let enumeration = dovetail(&alphabet, 100, 4, most_steps, &machine);
+        assert!(enumeration.undecided.is_empty());
+        assert_eq!(enumeration.accepted, ["abb", "aabb", "babb"]);
+
+        let enumeration = dovetail(&alphabet, 100, 4, most_steps - 1, &machine);
+        assert!(!enumeration.undecided.is_empty());
+    }
+}
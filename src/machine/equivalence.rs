@@ -0,0 +1,120 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::{
+    nfa,
+    subset::{Powerset, Subset},
+    symbols::shared_alphabet,
+};
+
+/// Outcome of comparing the languages of two automata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Equivalence {
+    Equivalent,
+    /// `witness` is a shortest string accepted by exactly one of the two machines
+    Different {
+        witness: String,
+        accepted_by_first: bool,
+    },
+}
+
+/// A state of each determinized machine
+type Pair = (Subset, Subset);
+
+/// Decides whether two finite automata accept the same language, by walking both of
+/// their determinized forms in lockstep until a pair of states disagrees on acceptance.
+/// The walk is breadth first, so the first disagreement comes with a shortest witness
+pub fn compare(first: &nfa::Info, second: &nfa::Info) -> Equivalence {
+    let alphabet = shared_alphabet([
+        (&first.table, &first.alphabet),
+        (&second.table, &second.alphabet),
+    ]);
+    let (first, second) = (Powerset::new(first), Powerset::new(second));
+
+    let start = (first.start(), second.start());
+    // How each pair was first reached, to spell the witness back out
+    let mut parents: HashMap<Pair, Option<(Pair, usize)>> = HashMap::from([(start.clone(), None)]);
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(pair) = queue.pop_front() {
+        let accepted_by_first = first.accepts(&pair.0);
+        if accepted_by_first != second.accepts(&pair.1) {
+            let mut symbols = vec![];
+            let mut current = &pair;
+            while let Some((parent, symbol)) = &parents[current] {
+                symbols.push(alphabet[*symbol].as_str());
+                current = parent;
+            }
+            symbols.reverse();
+
+            return Equivalence::Different {
+                witness: symbols.concat(),
+                accepted_by_first,
+            };
+        }
+
+        for (index, symbol) in alphabet.iter().enumerate() {
+            let next = (first.next(&pair.0, symbol), second.next(&pair.1, symbol));
+            if !parents.contains_key(&next) {
+                parents.insert(next.clone(), Some((pair.clone(), index)));
+                queue.push_back(next);
+            }
+        }
+    }
+
+    Equivalence::Equivalent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::regex::Regex;
+
+    fn nfa(pattern: &str) -> nfa::Info {
+        Regex::parse(pattern).unwrap().to_nfa(None)
+    }
+
+    #[test]
+    fn same_languages_are_equivalent() {
+        for (first, second) in [
+            ("(a|b)*", "(a*b*)*"),
+            ("a(ba)*", "(ab)*a"),
+            ("a+|ε", "a*"),
+            ("∅", "a∅b"),
+        ] {
+            assert_eq!(
+                compare(&nfa(first), &nfa(second)),
+                Equivalence::Equivalent,
+                "{first} and {second}"
+            );
+        }
+    }
+
+    #[test]
+    fn witnesses_are_shortest() {
+        assert_eq!(
+            compare(&nfa("(a|b)*abb"), &nfa("(a|b)*bb")),
+            Equivalence::Different {
+                witness: "bb".to_string(),
+                accepted_by_first: false,
+            }
+        );
+        assert_eq!(
+            compare(&nfa("a*"), &nfa("a+")),
+            Equivalence::Different {
+                witness: String::new(),
+                accepted_by_first: true,
+            }
+        );
+    }
+
+    #[test]
+    fn symbols_of_either_alphabet_are_tried() {
+        assert_eq!(
+            compare(&nfa("a*"), &nfa("(a|b)*")),
+            Equivalence::Different {
+                witness: "b".to_string(),
+                accepted_by_first: false,
+            }
+        );
+    }
+}
@@ -0,0 +1,465 @@
+use std::collections::{HashMap, HashSet};
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::parser::{Parser, ParserError, Token, TokenKind};
+
+use super::{
+    pda::{
+        self, Acceptance, PDATransitionFrom, PDATransitionTo, StackAction, StackSemantics, StackTop,
+    },
+    symbols::{SymbolId, SymbolTable},
+};
+
+#[derive(Error, Diagnostic, Debug)]
+pub enum GrammarError {
+    #[error("Unknown grammar symbol")]
+    #[diagnostic(help("declare it under [nonterminals] or [terminals]"))]
+    UnknownSymbol {
+        #[label("here")]
+        at: SourceSpan,
+    },
+    #[error("Unknown nonterminal")]
+    #[diagnostic(help("declare it under [nonterminals]"))]
+    UnknownNonterminal {
+        #[label("here")]
+        at: SourceSpan,
+    },
+    #[error("Symbol is both a terminal and a nonterminal")]
+    AmbiguousSymbol {
+        #[label("here")]
+        at: SourceSpan,
+        #[label("also declared here")]
+        other: SourceSpan,
+    },
+    #[error("Terminal must be a single character")]
+    #[diagnostic(help("inputs are read one character at a time"))]
+    LongTerminal {
+        #[label("here")]
+        at: SourceSpan,
+    },
+    #[error("Empty alternative")]
+    #[diagnostic(help("write ε for the empty string"))]
+    EmptyAlternative {
+        #[label("nothing before this")]
+        at: SourceSpan,
+    },
+}
+
+/// Context-free grammar, as written in a file with `[nonterminals]`, `[terminals]`,
+/// `[start]` and `[productions]` sections
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grammar {
+    pub nonterminals: Vec<String>,
+    pub terminals: Vec<String>,
+    pub start: String,
+    /// Each production as its head and its body, an empty body being ε
+    pub productions: Vec<(String, Vec<String>)>,
+}
+
+/// Separates the alternatives of a production
+const ALTERNATIVE: &str = "|";
+
+/// What a grammar symbol was declared as
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Declared {
+    Nonterminal,
+    Terminal,
+}
+
+impl Grammar {
+    /// Parses a grammar out of tokens lexed by [`Parser::lex`]. Productions are written
+    /// `S => a S b | ε`, one head per rule, and symbols may be run together like `aSb`
+    /// when the whole name isn't declared itself
+    pub fn parse(src: &str, input: Vec<Token>) -> miette::Result<Self> {
+        let mut nonterminals = None;
+        let mut terminals = None;
+        let mut start = None;
+        let mut productions = None;
+
+        let mut seen_sections: HashMap<&str, Token> = HashMap::new();
+        let input = &mut input.into_iter().peekable();
+
+        while let Some(section) = Parser::parse_section(input)? {
+            if let Some(token) = seen_sections.get(section.src(src)) {
+                return Err(ParserError::DuplicateSection {
+                    at: section.span(),
+                    other: token.span(),
+                }
+                .into());
+            }
+
+            match section.src(src) {
+                "nonterminals" => nonterminals = Some(Parser::parse_list_section(input)?),
+                "terminals" => terminals = Some(Parser::parse_list_section(input)?),
+                "start" => start = Some(Parser::parse_single_section(input)?),
+                "productions" => {
+                    let mut tokens = vec![];
+                    while let Some(token) = input.next_if(|token| {
+                        !matches!(token.kind(), TokenKind::LeftSquareBracket | TokenKind::EOF)
+                    }) {
+                        tokens.push(token);
+                    }
+                    productions = Some(tokens);
+                }
+                _ => {
+                    return Err(ParserError::UnknownSectionName { at: section.span() }.into());
+                }
+            }
+
+            seen_sections.insert(section.src(src), section);
+        }
+
+        let nonterminals = nonterminals.ok_or(ParserError::MissingSection {
+            section: "nonterminals",
+        })?;
+        let terminals = terminals.ok_or(ParserError::MissingSection {
+            section: "terminals",
+        })?;
+        let start = start.ok_or(ParserError::MissingSection { section: "start" })?;
+        let productions = productions.ok_or(ParserError::MissingSection {
+            section: "productions",
+        })?;
+
+        let mut declared: HashMap<&str, (Declared, SourceSpan)> = HashMap::new();
+        for (tokens, kind) in [
+            (&nonterminals, Declared::Nonterminal),
+            (&terminals, Declared::Terminal),
+        ] {
+            for token in tokens {
+                let name = token.src(src);
+                if kind == Declared::Terminal && name.chars().count() != 1 {
+                    return Err(GrammarError::LongTerminal { at: token.span() }.into());
+                }
+
+                match declared.get(name) {
+                    Some(&(other_kind, other)) if other_kind != kind => {
+                        return Err(GrammarError::AmbiguousSymbol {
+                            at: token.span(),
+                            other,
+                        }
+                        .into());
+                    }
+                    Some(_) => {}
+                    None => {
+                        declared.insert(name, (kind, token.span()));
+                    }
+                }
+            }
+        }
+
+        let is_nonterminal = |token: &Token| {
+            matches!(
+                declared.get(token.src(src)),
+                Some((Declared::Nonterminal, _))
+            )
+        };
+        if !is_nonterminal(&start) {
+            return Err(GrammarError::UnknownNonterminal { at: start.span() }.into());
+        }
+
+        let mut rules = vec![];
+        let mut index = 0;
+        while let Some(head) = productions.get(index) {
+            if head.kind() != &TokenKind::Identifier {
+                return Err(ParserError::UnexpectedToken {
+                    at: head.span(),
+                    expected: "<nonterminal>",
+                }
+                .into());
+            }
+            if !is_nonterminal(head) {
+                return Err(GrammarError::UnknownNonterminal { at: head.span() }.into());
+            }
+
+            // Assert arrow
+            let arrow = match productions.get(index + 1) {
+                Some(token) if token.kind() == &TokenKind::Arrow => token,
+                Some(token) => {
+                    return Err(ParserError::UnexpectedToken {
+                        at: token.span(),
+                        expected: "=>",
+                    }
+                    .into());
+                }
+                None => return Err(ParserError::UnexpectedEOF.into()),
+            };
+
+            // The body runs up to the head of the next rule, the token before an arrow
+            let body_start = index + 2;
+            index = (body_start..productions.len())
+                .find(|&next| {
+                    productions
+                        .get(next + 1)
+                        .is_some_and(|token| token.kind() == &TokenKind::Arrow)
+                })
+                .unwrap_or(productions.len());
+
+            let head = head.src(src).to_string();
+            let body = &productions[body_start..index];
+            for alternative in Self::alternatives(src, arrow, body, &declared)? {
+                rules.push((head.clone(), alternative));
+            }
+        }
+
+        let names = |tokens: &[Token]| -> Vec<String> {
+            let mut seen = HashSet::new();
+            tokens
+                .iter()
+                .map(|token| token.src(src).to_string())
+                .filter(|name| seen.insert(name.clone()))
+                .collect()
+        };
+
+        Ok(Grammar {
+            nonterminals: names(&nonterminals),
+            terminals: names(&terminals),
+            start: start.src(src).to_string(),
+            productions: rules,
+        })
+    }
+
+    /// Splits the body of a rule into its alternatives, each a list of symbol names
+    fn alternatives(
+        src: &str,
+        arrow: &Token,
+        body: &[Token],
+        declared: &HashMap<&str, (Declared, SourceSpan)>,
+    ) -> miette::Result<Vec<Vec<String>>> {
+        let mut alternatives = vec![];
+        let mut current: Vec<String> = vec![];
+        // Whether the current alternative has anything written in it, ε included
+        let mut written = false;
+        let mut separator = arrow.span();
+
+        for token in body {
+            if token.kind() != &TokenKind::Identifier {
+                return Err(ParserError::UnexpectedToken {
+                    at: token.span(),
+                    expected: "<symbol>, ε or |",
+                }
+                .into());
+            }
+
+            let name = token.src(src);
+            let pieces: Vec<(&str, SourceSpan)> =
+                if name == ALTERNATIVE || name == "ε" || declared.contains_key(name) {
+                    vec![(name, token.span())]
+                } else {
+                    // Not a symbol of its own, so `aSb` stands for `a`, `S`, `b`
+                    name.char_indices()
+                        .map(|(i, c)| {
+                            let at =
+                                SourceSpan::new((token.span().offset() + i).into(), c.len_utf8());
+                            (&name[i..i + c.len_utf8()], at)
+                        })
+                        .collect()
+                };
+
+            for (piece, at) in pieces {
+                match piece {
+                    ALTERNATIVE => {
+                        if !written {
+                            return Err(GrammarError::EmptyAlternative { at }.into());
+                        }
+                        alternatives.push(std::mem::take(&mut current));
+                        written = false;
+                        separator = at;
+                    }
+                    "ε" => written = true,
+                    piece if declared.contains_key(piece) => {
+                        current.push(piece.to_string());
+                        written = true;
+                    }
+                    _ => return Err(GrammarError::UnknownSymbol { at }.into()),
+                }
+            }
+        }
+
+        if !written {
+            let at = SourceSpan::new((separator.offset() + separator.len()).into(), 0);
+            return Err(GrammarError::EmptyAlternative { at }.into());
+        }
+        alternatives.push(current);
+
+        Ok(alternatives)
+    }
+
+    /// PDA accepting the language of the grammar, by the standard single state
+    /// construction: the stack starts with the start symbol, a nonterminal on top is
+    /// replaced by the body of one of its productions on epsilon, and a terminal on top
+    /// is popped by reading it. The input is accepted once the stack is empty
+    pub fn to_pda(&self) -> pda::Info {
+        let mut table = SymbolTable::new();
+        let state = table.intern_state("q");
+
+        let nonterminals: Vec<SymbolId> = self
+            .nonterminals
+            .iter()
+            .map(|name| table.intern_symbol(name))
+            .collect();
+        let terminals: Vec<SymbolId> = self
+            .terminals
+            .iter()
+            .map(|name| table.intern_symbol(name))
+            .collect();
+        let symbol = |name: &str| table.symbol(name).expect("grammar symbols are declared");
+
+        let mut transitions: HashMap<PDATransitionFrom, Vec<PDATransitionTo>> = HashMap::new();
+        for (head, body) in &self.productions {
+            let from = PDATransitionFrom {
+                initial: state,
+                with_symbol: SymbolId::EPSILON,
+                stack_top: StackTop::Symbol(symbol(head)),
+            };
+            transitions.entry(from).or_default().push(PDATransitionTo {
+                state,
+                stack_action: StackAction::Replace(body.iter().map(|name| symbol(name)).collect()),
+            });
+        }
+
+        for &terminal in &terminals {
+            let from = PDATransitionFrom {
+                initial: state,
+                with_symbol: terminal,
+                stack_top: StackTop::Symbol(terminal),
+            };
+            transitions.entry(from).or_default().push(PDATransitionTo {
+                state,
+                stack_action: StackAction::Pop,
+            });
+        }
+
+        let start_stack_symbol = Some(symbol(&self.start));
+        pda::Info {
+            alphabet: terminals
+                .iter()
+                .copied()
+                .chain([SymbolId::EPSILON])
+                .collect(),
+            stack_alphabet: nonterminals.iter().chain(&terminals).copied().collect(),
+            table,
+            transitions,
+            start_state: state,
+            final_states: HashSet::new(),
+            start_stack_symbol,
+            acceptance: Acceptance::EmptyStack,
+            stack_semantics: StackSemantics::Lenient,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{Machine as _, strings_up_to};
+
+    fn grammar(src: &str) -> Grammar {
+        Grammar::parse(src, Parser::lex(src).unwrap()).unwrap()
+    }
+
+    /// Checks the PDA built out of the grammar against `expected` on every string over
+    /// `alphabet` of at most `max_length` characters
+    fn assert_language(src: &str, alphabet: &str, max_length: usize, expected: fn(&str) -> bool) {
+        let mut machine = pda::Machine::new(grammar(src).to_pda());
+        for input in strings_up_to(alphabet, max_length) {
+            assert_eq!(
+                machine.run(&input).accepted(),
+                expected(&input),
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn splits_symbols_written_together() {
+        let grammar = grammar(
+            "
+[nonterminals]
+S
+[terminals]
+a, b
+[start]
+S
+[productions]
+S => aSb | ε
+",
+        );
+
+        assert_eq!(
+            grammar.productions,
+            [
+                (
+                    "S".to_string(),
+                    vec!["a".to_string(), "S".to_string(), "b".to_string()]
+                ),
+                ("S".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn pda_accepts_anbn() {
+        let src = "
+[nonterminals]
+S
+[terminals]
+a, b
+[start]
+S
+[productions]
+S => aSb | ε
+";
+        assert_language(src, "ab", 8, |input| {
+            let half = input.len() / 2;
+            input == format!("{}{}", "a".repeat(half), "b".repeat(half))
+        });
+    }
+
+    #[test]
+    fn pda_accepts_balanced_brackets() {
+        // Parentheses separate tokens in machine files, so the brackets are angled
+        let src = "
+[nonterminals]
+S
+[terminals]
+<, >
+[start]
+S
+[productions]
+S => < S > S | ε
+";
+        assert_language(src, "<>", 8, |input| {
+            let mut depth = 0;
+            for c in input.chars() {
+                depth += if c == '<' { 1 } else { -1 };
+                if depth < 0 {
+                    return false;
+                }
+            }
+            depth == 0
+        });
+    }
+
+    #[test]
+    fn pda_accepts_through_nullable_chains() {
+        let src = "
+[nonterminals]
+S, A, B, C
+[terminals]
+a, b
+[start]
+S
+[productions]
+S => A A A A A A A A a B
+A => C | ε
+C => A
+B => b B | A
+";
+        // `A` and `C` only derive ε, going round in circles, and `B` any number of `b`
+        assert_language(src, "ab", 6, |input| {
+            input.starts_with('a') && input[1..].chars().all(|c| c == 'b')
+        });
+    }
+}
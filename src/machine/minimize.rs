@@ -0,0 +1,261 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    TransitionFrom, TransitionTo, dfa, reachable,
+    symbols::{StateId, SymbolId, SymbolTable},
+};
+
+/// Minimal DFA along with how it relates to the original one
+#[derive(Debug, Clone)]
+pub struct Minimized {
+    pub dfa: dfa::Info,
+    /// Original states merged into each state of the minimal DFA, indexed by the new
+    /// state ids. Every class is named after its first state
+    pub classes: Vec<Vec<StateId>>,
+    /// Original states that can't be reached from the initial state, and were dropped
+    pub unreachable: Vec<StateId>,
+}
+
+/// States reachable from the initial state, in declaration order
+fn reachable_states(info: &dfa::Info, alphabet: &[SymbolId]) -> Vec<StateId> {
+    let seen = reachable([info.start_state], |state| {
+        alphabet.iter().map(move |&symbol| info.next(state, symbol))
+    });
+
+    let mut states: Vec<StateId> = seen.into_iter().collect();
+    states.sort_unstable();
+    states
+}
+
+/// Computes the minimal DFA accepting the same language, using Hopcroft's partition
+/// refinement on the reachable part of the DFA
+pub fn minimize(info: &dfa::Info) -> Minimized {
+    let mut alphabet: Vec<SymbolId> = info.alphabet.iter().copied().collect();
+    alphabet.sort_unstable();
+
+    let reachable = reachable_states(info, &alphabet);
+    let unreachable = info
+        .table
+        .states()
+        .filter(|state| reachable.binary_search(state).is_err())
+        .collect();
+
+    // Moves leading into each state, per symbol
+    let mut incoming: HashMap<(StateId, SymbolId), Vec<StateId>> = HashMap::new();
+    for &state in &reachable {
+        for &symbol in &alphabet {
+            incoming
+                .entry((info.next(state, symbol), symbol))
+                .or_default()
+                .push(state);
+        }
+    }
+
+    // Start from accepting against non accepting states
+    let (accepting, rejecting): (Vec<StateId>, Vec<StateId>) = reachable
+        .iter()
+        .partition(|state| info.final_states.contains(state));
+    let mut blocks: Vec<Vec<StateId>> = [accepting, rejecting]
+        .into_iter()
+        .filter(|block| !block.is_empty())
+        .collect();
+    let mut block_of: HashMap<StateId, usize> = HashMap::new();
+    for (index, block) in blocks.iter().enumerate() {
+        for &state in block {
+            block_of.insert(state, index);
+        }
+    }
+
+    let mut pending: Vec<usize> = (0..blocks.len()).collect();
+    let mut is_pending = vec![true; blocks.len()];
+
+    while let Some(splitter) = pending.pop() {
+        is_pending[splitter] = false;
+        let splitter_states = blocks[splitter].clone();
+
+        for &symbol in &alphabet {
+            // States moving into the splitter on this symbol
+            let movers: HashSet<StateId> = splitter_states
+                .iter()
+                .filter_map(|&state| incoming.get(&(state, symbol)))
+                .flatten()
+                .copied()
+                .collect();
+
+            let touched: HashSet<usize> = movers.iter().map(|state| block_of[state]).collect();
+            let mut touched: Vec<usize> = touched.into_iter().collect();
+            touched.sort_unstable();
+
+            for block in touched {
+                let (inside, outside): (Vec<StateId>, Vec<StateId>) = blocks[block]
+                    .iter()
+                    .partition(|state| movers.contains(state));
+                if outside.is_empty() {
+                    continue;
+                }
+
+                // The block keeps the states moving into the splitter, the rest
+                // become a new block
+                let new_block = blocks.len();
+                for &state in &outside {
+                    block_of.insert(state, new_block);
+                }
+                let smaller_is_new = outside.len() <= inside.len();
+                blocks[block] = inside;
+                blocks.push(outside);
+                is_pending.push(false);
+
+                if is_pending[block] {
+                    pending.push(new_block);
+                    is_pending[new_block] = true;
+                } else {
+                    let smaller = if smaller_is_new { new_block } else { block };
+                    pending.push(smaller);
+                    is_pending[smaller] = true;
+                }
+            }
+        }
+    }
+
+    // Number the classes in the declaration order of their first state
+    let mut classes = blocks;
+    for class in &mut classes {
+        class.sort_unstable();
+    }
+    classes.sort_unstable_by_key(|class| class[0]);
+    for (index, class) in classes.iter().enumerate() {
+        for &state in class {
+            block_of.insert(state, index);
+        }
+    }
+
+    let mut table = SymbolTable::new();
+    let new_states: Vec<StateId> = classes
+        .iter()
+        .map(|class| table.intern_state(info.table.state_name(class[0])))
+        .collect();
+    let new_alphabet: Vec<SymbolId> = alphabet
+        .iter()
+        .map(|&symbol| table.intern_symbol(info.table.symbol_name(symbol)))
+        .collect();
+
+    let mut transitions = HashMap::new();
+    let mut final_states = HashSet::new();
+    for (index, class) in classes.iter().enumerate() {
+        let representative = class[0];
+        if info.final_states.contains(&representative) {
+            final_states.insert(new_states[index]);
+        }
+
+        for (&symbol, &new_symbol) in alphabet.iter().zip(&new_alphabet) {
+            let target = block_of[&info.next(representative, symbol)];
+
+            transitions.insert(
+                TransitionFrom {
+                    initial: new_states[index],
+                    with_symbol: new_symbol,
+                },
+                TransitionTo(new_states[target]),
+            );
+        }
+    }
+
+    Minimized {
+        dfa: dfa::Info {
+            table,
+            alphabet: new_alphabet.into_iter().collect(),
+            transitions,
+            start_state: new_states[block_of[&info.start_state]],
+            final_states,
+        },
+        classes,
+        unreachable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{
+        equivalence::{self, Equivalence},
+        nfa, parse, subset,
+    };
+
+    fn dfas() -> Vec<dfa::Info> {
+        let mut dfas: Vec<dfa::Info> = [
+            include_str!("../../dfa.txt"),
+            include_str!("../../dfa_level1_escape.txt"),
+            include_str!("../../dfa_level2_escape_with_key.txt"),
+        ]
+        .into_iter()
+        .map(|src| dfa::Info::new(parse(src), src).unwrap())
+        .collect();
+
+        // Subset constructions leave plenty of states to merge
+        for src in [
+            include_str!("../../nfa_accept.txt"),
+            include_str!("../../nfa_complex.txt"),
+        ] {
+            dfas.push(subset::determinize(
+                &nfa::Info::new(parse(src), src).unwrap(),
+            ));
+        }
+        dfas
+    }
+
+    fn as_nfa(info: &dfa::Info) -> nfa::Info {
+        let written = info.to_string();
+        nfa::Info::new(parse(&written), &written).unwrap()
+    }
+
+    #[test]
+    fn minimal_dfas_read_back_as_equivalent() {
+        for dfa in dfas() {
+            let minimal = minimize(&dfa).dfa;
+            assert_eq!(
+                equivalence::compare(&as_nfa(&dfa), &as_nfa(&minimal)),
+                Equivalence::Equivalent
+            );
+        }
+    }
+
+    #[test]
+    fn minimizing_twice_changes_nothing() {
+        for dfa in dfas() {
+            let once = minimize(&dfa);
+            let twice = minimize(&once.dfa);
+
+            assert!(once.dfa.table.states().count() <= dfa.table.states().count());
+            assert_eq!(
+                twice.dfa.table.states().count(),
+                once.dfa.table.states().count()
+            );
+            assert!(twice.unreachable.is_empty());
+            assert!(twice.classes.iter().all(|class| class.len() == 1));
+        }
+    }
+
+    #[test]
+    fn merges_equivalent_states_and_drops_unreachable_ones() {
+        let src = "
+[states]
+q0, q1, q2, q3
+[alphabet]
+a
+[initial]
+q0
+[final]
+q1, q2
+[transitions]
+q0(a) => (q1)
+q1(a) => (q2)
+q2(a) => (q1)
+q3(a) => (q0)
+";
+        let minimal = minimize(&dfa::Info::new(parse(src), src).unwrap());
+
+        assert_eq!(minimal.dfa.table.states().count(), 2);
+        assert_eq!(minimal.classes.iter().map(Vec::len).max(), Some(2));
+        assert_eq!(minimal.unreachable.len(), 1);
+    }
+}
@@ -1,13 +1,430 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt,
+    hash::Hash,
+};
+
+use serde::Serialize;
+
+use crate::diagram::Diagram;
+use crate::parser::{ParserError, PartialMachineInfo, StackTransition};
+
+pub mod analysis;
+pub mod closure;
 pub mod dfa;
+pub mod elimination;
+pub mod enumerate;
+pub mod equivalence;
+pub mod grammar;
+pub mod minimize;
 pub mod nfa;
 pub mod pda;
+pub mod product;
+pub mod regex;
+pub mod subset;
+pub mod suite;
+pub mod symbols;
 pub mod tm;
 
+use symbols::{StateId, SymbolId};
+
+/// Everything reachable from `roots` following `edges`, which gives the nodes one move
+/// away from a node. Shared by every search over the states of a machine
+pub fn reachable<T, I>(
+    roots: impl IntoIterator<Item = T>,
+    mut edges: impl FnMut(T) -> I,
+) -> HashSet<T>
+where
+    T: Copy + Eq + Hash,
+    I: IntoIterator<Item = T>,
+{
+    let mut queue: VecDeque<T> = roots.into_iter().collect();
+    let mut seen: HashSet<T> = queue.iter().copied().collect();
+    while let Some(node) = queue.pop_front() {
+        for next in edges(node) {
+            if seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    seen
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct TransitionFrom {
-    pub initial: &'static str,
-    pub with_symbol: &'static str,
+    pub initial: StateId,
+    pub with_symbol: SymbolId,
 }
 
 #[derive(Debug, Clone)]
-pub struct TransitionTo(pub &'static str);
+pub struct TransitionTo(pub StateId);
+
+/// Why a machine rejected its input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Rejection {
+    /// The input contains a character that is not part of the alphabet
+    UnknownSymbol { symbol: char },
+    /// The machine got stuck: nothing applies to the current configuration
+    NoTransition {
+        state: String,
+        symbol: String,
+        stack_top: Option<String>,
+    },
+    /// The machine halted outside of a final state
+    NotAccepting { state: String },
+    /// The PDA read its whole input without emptying its stack, top first
+    StackNotEmpty { state: String, stack: Vec<String> },
+    /// The PDA tried to pop its empty stack, which strict stack semantics forbid
+    EmptyStackPopped { state: String },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::UnknownSymbol { symbol } => write!(f, "symbol '{symbol}' not in alphabet"),
+            Rejection::NoTransition {
+                state,
+                symbol,
+                stack_top: None,
+            } => write!(f, "no transition from state {state} on '{symbol}'"),
+            Rejection::NoTransition {
+                state,
+                symbol,
+                stack_top: Some(top),
+            } => write!(
+                f,
+                "no transition from state {state} on '{symbol}' with stack top '{top}'"
+            ),
+            Rejection::NotAccepting { state } => write!(f, "halted in non-final state {state}"),
+            Rejection::StackNotEmpty { state, stack } => write!(
+                f,
+                "halted in state {state} with [{}] left on the stack",
+                stack.join(", ")
+            ),
+            Rejection::EmptyStackPopped { state } => {
+                write!(f, "tried to pop the empty stack in state {state}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Accepted,
+    Rejected(Rejection),
+}
+
+/// Result of advancing a machine by a single step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// The machine moved and can keep going
+    Continue,
+    /// The machine halted
+    Halt(Verdict),
+}
+
+/// Snapshot of everything a machine needs to know to carry on with a run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Configuration {
+    Dfa {
+        state: String,
+        remaining_input: String,
+    },
+    Nfa {
+        /// Active states, after their epsilon closure
+        states: Vec<String>,
+        remaining_input: String,
+    },
+    Pda {
+        state: String,
+        remaining_input: String,
+        /// Stack contents, top first
+        stack: Vec<String>,
+    },
+    Tm {
+        state: String,
+        tape: Vec<String>,
+        head: usize,
+    },
+}
+
+impl fmt::Display for Configuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Configuration::Dfa {
+                state,
+                remaining_input,
+            } => write!(f, "({state}, \"{remaining_input}\")"),
+            Configuration::Nfa {
+                states,
+                remaining_input,
+            } => write!(f, "({{{}}}, \"{remaining_input}\")", states.join(", ")),
+            Configuration::Pda {
+                state,
+                remaining_input,
+                stack,
+            } => write!(
+                f,
+                "({state}, \"{remaining_input}\", [{}])",
+                stack.join(", ")
+            ),
+            Configuration::Tm { state, tape, head } => {
+                write!(f, "({state}, Tape [")?;
+                for (index, symbol) in tape.iter().enumerate() {
+                    if index == *head {
+                        write!(f, "[{symbol}]")?;
+                    } else {
+                        write!(f, " {symbol} ")?;
+                    }
+                }
+                write!(f, "])")
+            }
+        }
+    }
+}
+
+impl Configuration {
+    /// Names of the states the machine is in, several of them only for NFAs
+    pub fn states(&self) -> Vec<&str> {
+        match self {
+            Configuration::Nfa { states, .. } => states.iter().map(String::as_str).collect(),
+            Configuration::Dfa { state, .. }
+            | Configuration::Pda { state, .. }
+            | Configuration::Tm { state, .. } => vec![state],
+        }
+    }
+}
+
+/// Everything known about a finished run
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub verdict: Verdict,
+    /// Number of moves made before halting
+    pub steps: usize,
+    /// Configuration the machine halted in
+    pub configuration: Configuration,
+}
+
+impl Outcome {
+    pub fn accepted(&self) -> bool {
+        self.verdict == Verdict::Accepted
+    }
+}
+
+/// What is written on a Turing machine's tape
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapeContents {
+    pub cells: Vec<String>,
+    pub blank: String,
+}
+
+impl TapeContents {
+    /// Spells out the tape without the blanks surrounding its contents
+    pub fn trimmed(&self) -> String {
+        let start = self.cells.iter().position(|cell| *cell != self.blank);
+        let end = self.cells.iter().rposition(|cell| *cell != self.blank);
+
+        match (start, end) {
+            (Some(start), Some(end)) => self.cells[start..=end].concat(),
+            _ => String::new(),
+        }
+    }
+
+    /// Compares against a spelled out tape, ignoring the blanks surrounding either of them
+    pub fn matches(&self, expected: &str) -> bool {
+        let expected = expected
+            .trim_start_matches(self.blank.as_str())
+            .trim_end_matches(self.blank.as_str());
+
+        self.trimmed() == expected
+    }
+}
+
+/// Common interface shared by every kind of automaton, so the CLI can drive them
+/// through dynamic dispatch instead of matching on each kind
+pub trait Machine {
+    /// Validates the parsed file for this kind of machine and builds it
+    fn build(machine: PartialMachineInfo, src: &str) -> miette::Result<Self>
+    where
+        Self: Sized;
+
+    /// Copy of the machine, to run several inputs side by side without building it again
+    fn clone_box(&self) -> Box<dyn Machine>;
+
+    /// Puts the machine back in its initial configuration with `input` loaded
+    fn reset(&mut self, input: &str);
+
+    /// Advances the machine by one move
+    fn step(&mut self) -> Step;
+
+    /// Snapshot of the current configuration
+    fn configuration(&self) -> Configuration;
+
+    /// Human readable description of the current configuration
+    fn describe(&self) -> String {
+        self.configuration().to_string()
+    }
+
+    /// Current tape contents, for machines that have a tape
+    fn tape(&self) -> Option<TapeContents> {
+        None
+    }
+
+    /// Runs the machine on `input` until it halts
+    fn run(&mut self, input: &str) -> Outcome {
+        self.run_traced(input, &mut |_| {})
+    }
+
+    /// Same as [`Machine::run`], but `on_step` is handed the initial configuration and
+    /// every configuration reached after it
+    fn run_traced(&mut self, input: &str, on_step: &mut dyn FnMut(&Configuration)) -> Outcome {
+        self.reset(input);
+        on_step(&self.configuration());
+
+        let mut steps = 0;
+        loop {
+            match self.step() {
+                Step::Continue => {
+                    steps += 1;
+                    on_step(&self.configuration());
+                }
+                Step::Halt(verdict) => {
+                    return Outcome {
+                        verdict,
+                        steps,
+                        configuration: self.configuration(),
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineKind {
+    Dfa,
+    Nfa,
+    Pda,
+    Tm,
+}
+
+impl MachineKind {
+    /// Reads the kind declared in the `[type]` section, if there is one
+    pub fn declared(machine: &PartialMachineInfo, src: &str) -> miette::Result<Option<Self>> {
+        let Some(token) = &machine.kind else {
+            return Ok(None);
+        };
+
+        let kind = match token.src(src).to_ascii_lowercase().as_str() {
+            "dfa" => MachineKind::Dfa,
+            "nfa" => MachineKind::Nfa,
+            "pda" => MachineKind::Pda,
+            "tm" => MachineKind::Tm,
+            _ => return Err(ParserError::UnknownMachineType { at: token.span() }.into()),
+        };
+
+        Ok(Some(kind))
+    }
+
+    /// Infers the most specific kind of machine able to run the parsed file
+    pub fn detect(machine: &PartialMachineInfo, src: &str) -> Self {
+        let uses_tape = machine.tape_alphabet.is_some()
+            || machine.blank_symbol.is_some()
+            || machine.transitions.iter().any(|transition| {
+                matches!(transition.to.1, Some(StackTransition::Write(..)))
+                    || transition.to.2.is_some()
+            });
+        if uses_tape {
+            return MachineKind::Tm;
+        }
+
+        let uses_stack = machine.stack_alphabet.is_some()
+            || machine.start_stack.is_some()
+            || machine.acceptance.is_some()
+            || machine.stack_semantics.is_some()
+            || machine.transitions.iter().any(|transition| {
+                transition.from.with_stack_symbol.is_some() || transition.to.1.is_some()
+            });
+        if uses_stack {
+            return MachineKind::Pda;
+        }
+
+        // Epsilon moves or several moves on the same symbol need an NFA
+        let mut seen = HashSet::new();
+        for transition in &machine.transitions {
+            let symbol = transition.from.with_symbol.src(src);
+            if symbol == "ε" || !seen.insert((transition.from.initial.src(src), symbol)) {
+                return MachineKind::Nfa;
+            }
+        }
+
+        // Deterministic, but a DFA also needs a move for every state and symbol
+        let complete = machine.states.iter().all(|state| {
+            machine
+                .alphabet
+                .iter()
+                .all(|symbol| seen.contains(&(state.src(src), symbol.src(src))))
+        });
+
+        if complete {
+            MachineKind::Dfa
+        } else {
+            MachineKind::Nfa
+        }
+    }
+
+    pub fn build(self, machine: PartialMachineInfo, src: &str) -> miette::Result<Box<dyn Machine>> {
+        Ok(match self {
+            MachineKind::Dfa => Box::new(dfa::Machine::build(machine, src)?),
+            MachineKind::Nfa => Box::new(nfa::Machine::build(machine, src)?),
+            MachineKind::Pda => Box::new(pda::Machine::build(machine, src)?),
+            MachineKind::Tm => Box::new(tm::Machine::build(machine, src)?),
+        })
+    }
+
+    /// Validates the parsed file for this kind of machine and draws its state diagram
+    pub fn diagram(self, machine: PartialMachineInfo, src: &str) -> miette::Result<Diagram> {
+        Ok(match self {
+            MachineKind::Dfa => dfa::Info::new(machine, src)?.diagram(),
+            MachineKind::Nfa => nfa::Info::new(machine, src)?.diagram(),
+            MachineKind::Pda => pda::Info::new(machine, src)?.diagram(),
+            MachineKind::Tm => tm::Info::new(machine, src)?.diagram(),
+        })
+    }
+}
+
+impl fmt::Display for MachineKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MachineKind::Dfa => write!(f, "DFA"),
+            MachineKind::Nfa => write!(f, "NFA"),
+            MachineKind::Pda => write!(f, "PDA"),
+            MachineKind::Tm => write!(f, "TM"),
+        }
+    }
+}
+
+/// Parses a machine file written out in a test
+#[cfg(test)]
+pub(crate) fn parse(src: &str) -> PartialMachineInfo {
+    crate::parser::Parser::lex(src)
+        .and_then(|tokens| crate::parser::Parser::parse(src, tokens))
+        .unwrap()
+}
+
+/// Every string over `alphabet` of at most `max_length` characters, shortest first
+#[cfg(test)]
+pub(crate) fn strings_up_to(alphabet: &str, max_length: usize) -> Vec<String> {
+    let mut strings = vec![String::new()];
+    let mut level = vec![String::new()];
+    for _ in 0..max_length {
+        level = level
+            .iter()
+            .flat_map(|prefix| alphabet.chars().map(move |c| format!("{prefix}{c}")))
+            .collect();
+        strings.extend(level.iter().cloned());
+    }
+    strings
+}
@@ -1,11 +1,18 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 use miette::Diagnostic;
 use thiserror::Error;
 
+use crate::diagram::Diagram;
 use crate::parser::{ParserError, PartialMachineInfo, StackTransition};
 
-use super::{TransitionFrom, TransitionTo};
+use super::{
+    Configuration, Rejection, Step, TransitionFrom, TransitionTo, Verdict,
+    symbols::{StateId, SymbolId, SymbolTable},
+};
 
 #[derive(Error, Diagnostic, Debug)]
 pub enum NFAError {
@@ -17,16 +24,15 @@ pub enum NFAError {
 
 #[derive(Debug, Clone)]
 pub struct Info {
-    // We actually never need the full states hashset
-    // pub states: HashSet<&'static str>,
-    pub alphabet: HashSet<&'static str>,
+    pub table: SymbolTable,
+    pub alphabet: HashSet<SymbolId>,
     pub transitions: HashMap<TransitionFrom, Vec<TransitionTo>>,
-    pub start_state: &'static str,
-    pub final_states: HashSet<&'static str>,
+    pub start_state: StateId,
+    pub final_states: HashSet<StateId>,
 }
 
 impl Info {
-    pub fn new(machine: PartialMachineInfo, src: &'static str) -> miette::Result<Self> {
+    pub fn new(machine: PartialMachineInfo, src: &str) -> miette::Result<Self> {
         if machine.stack_alphabet.is_some() || machine.start_stack.is_some() {
             return Err(NFAError::StackOperationsNotAllowed.into());
         }
@@ -35,41 +41,38 @@ impl Info {
             return Err(NFAError::TapeOperationsNotAllowed.into());
         }
 
-        let mut states = HashSet::new();
+        let mut table = SymbolTable::new();
         let mut alphabet = HashSet::new();
         let mut transitions = HashMap::new();
         let mut final_states = HashSet::new();
 
         for state in machine.states {
-            states.insert(state.src(src));
+            table.intern_state(state.src(src));
         }
 
         for symbol in machine.alphabet {
-            alphabet.insert(symbol.src(src));
+            alphabet.insert(table.intern_symbol(symbol.src(src)));
         }
 
-        alphabet.insert("ε");
+        alphabet.insert(SymbolId::EPSILON);
 
         for final_state in machine.final_states {
-            let state_str = final_state.src(src);
-
-            if !states.contains(state_str) {
+            let Some(state) = table.state(final_state.src(src)) else {
                 return Err(ParserError::UnknownState {
                     at: final_state.span(),
                 }
                 .into());
-            }
+            };
 
-            final_states.insert(state_str);
+            final_states.insert(state);
         }
 
-        let start_state = machine.start_state.src(src);
-        if !states.contains(start_state) {
+        let Some(start_state) = table.state(machine.start_state.src(src)) else {
             return Err(ParserError::UnknownState {
                 at: machine.start_state.span(),
             }
             .into());
-        }
+        };
 
         for transition in machine.transitions {
             if transition.from.with_stack_symbol.is_some() || transition.to.1.is_some() {
@@ -80,6 +83,7 @@ impl Info {
             if let Some(ref stack_trans) = transition.to.1 {
                 match stack_trans {
                     StackTransition::Push(_, _)
+                    | StackTransition::Replace(_, _)
                     | StackTransition::Pop(_)
                     | StackTransition::NoOp(_) => {
                         return Err(NFAError::StackOperationsNotAllowed.into());
@@ -95,31 +99,30 @@ impl Info {
                 return Err(NFAError::TapeOperationsNotAllowed.into());
             }
 
-            let from_state = transition.from.initial.src(src);
-            let symbol = transition.from.with_symbol.src(src);
-            let to_state = transition.to.0.src(src);
-
             // Validate transition states and symbols
-            if !states.contains(from_state) {
+            let Some(from_state) = table.state(transition.from.initial.src(src)) else {
                 return Err(ParserError::UnknownState {
                     at: transition.from.initial.span(),
                 }
                 .into());
-            }
+            };
 
-            if !states.contains(to_state) {
+            let Some(to_state) = table.state(transition.to.0.src(src)) else {
                 return Err(ParserError::UnknownState {
                     at: transition.to.0.span(),
                 }
                 .into());
-            }
+            };
 
-            if !alphabet.contains(symbol) {
+            let Some(symbol) = table
+                .symbol(transition.from.with_symbol.src(src))
+                .filter(|symbol| alphabet.contains(symbol))
+            else {
                 return Err(ParserError::UnknownAlphabetSymbol {
                     at: transition.from.with_symbol.span(),
                 }
                 .into());
-            }
+            };
 
             let key = TransitionFrom {
                 initial: from_state,
@@ -133,70 +136,161 @@ impl Info {
         }
 
         Ok(Info {
+            table,
             alphabet,
             transitions,
             start_state,
             final_states,
         })
     }
+
+    /// Every state reachable from `states` through epsilon moves alone, `states`
+    /// included
+    pub fn epsilon_closure(&self, states: HashSet<StateId>) -> HashSet<StateId> {
+        let mut closure = states.clone();
+        let mut stack = states.into_iter().collect::<Vec<StateId>>();
+
+        while let Some(state) = stack.pop() {
+            let key = TransitionFrom {
+                initial: state,
+                with_symbol: SymbolId::EPSILON,
+            };
+
+            if let Some(transitions) = self.transitions.get(&key) {
+                for transition in transitions {
+                    let target_state = transition.0;
+                    if !closure.contains(&target_state) {
+                        closure.insert(target_state);
+                        stack.push(target_state);
+                    }
+                }
+            }
+        }
+
+        closure
+    }
+
+    /// State diagram of the NFA, epsilon moves are labelled `ε`
+    pub fn diagram(&self) -> Diagram {
+        let mut diagram = Diagram::new(&self.table, self.start_state, &self.final_states);
+
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by_key(|(from, _)| (from.initial, from.with_symbol));
+
+        for (from, targets) in transitions {
+            for to in targets {
+                let label = self.table.symbol_name(from.with_symbol).to_string();
+                diagram.add_edge(from.initial, to.0, label);
+            }
+        }
+
+        diagram
+    }
+}
+
+/// Writes the NFA back out in the text format it is parsed from
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = &self.table;
+
+        let states: Vec<&str> = table
+            .states()
+            .map(|state| table.state_name(state))
+            .collect();
+        writeln!(f, "[states]\n{}\n", states.join(", "))?;
+
+        // Epsilon is part of every alphabet without being declared
+        let mut alphabet: Vec<SymbolId> = self
+            .alphabet
+            .iter()
+            .copied()
+            .filter(|&symbol| symbol != SymbolId::EPSILON)
+            .collect();
+        alphabet.sort_unstable();
+        let symbols: Vec<&str> = alphabet
+            .iter()
+            .map(|&symbol| table.symbol_name(symbol))
+            .collect();
+        writeln!(f, "[alphabet]\n{}\n", symbols.join(", "))?;
+
+        writeln!(f, "[initial]\n{}\n", table.state_name(self.start_state))?;
+
+        let mut final_states: Vec<StateId> = self.final_states.iter().copied().collect();
+        final_states.sort_unstable();
+        let final_states: Vec<&str> = final_states
+            .iter()
+            .map(|&state| table.state_name(state))
+            .collect();
+        writeln!(f, "[final]\n{}\n", final_states.join(", "))?;
+
+        writeln!(f, "[transitions]")?;
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by_key(|(from, _)| (from.initial, from.with_symbol));
+        for (from, targets) in transitions {
+            for to in targets {
+                writeln!(
+                    f,
+                    "{}({}) => ({})",
+                    table.state_name(from.initial),
+                    table.symbol_name(from.with_symbol),
+                    table.state_name(to.0)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Machine {
     info: Info,
-    current_states: HashSet<&'static str>,
+    current_states: HashSet<StateId>,
+    input: Vec<char>,
+    position: usize,
 }
 
 impl Machine {
     pub fn new(info: Info) -> Self {
         // Start with the initial state and its epsilon closure
-        let initial_states = HashSet::from([info.start_state]);
-        let current_states = Self::compute_epsilon_closure(&info, initial_states);
+        let current_states = Self::initial_states(&info);
 
         Self {
             info,
             current_states,
+            input: vec![],
+            position: 0,
         }
     }
 
-    pub fn run(mut self, input: &str) -> bool {
-        // Process each character in the input
-        for c in input.chars() {
-            let symbol = self.find_symbol(c);
+    fn initial_states(info: &Info) -> HashSet<StateId> {
+        info.epsilon_closure(HashSet::from([info.start_state]))
+    }
 
-            if let Some(symbol) = symbol {
-                self.current_states = self.get_next_states(symbol);
-                if self.current_states.is_empty() {
-                    println!(
-                        "No valid transitions found for symbol '{}'. Counting as not accepted",
-                        c
-                    );
+    /// Names of the active states, in declaration order
+    fn state_names(&self) -> Vec<String> {
+        let mut states: Vec<StateId> = self.current_states.iter().copied().collect();
+        states.sort_unstable();
 
-                    return false;
-                }
-            } else {
-                // Symbol not in alphabet
-                println!("Symbol '{}' not in alphabet. Counting as not accepted", c);
-                return false;
-            }
-        }
+        states
+            .into_iter()
+            .map(|state| self.info.table.state_name(state).to_string())
+            .collect()
+    }
 
-        // check if any of the current states are final states
-        self.current_states
-            .iter()
-            .any(|state| self.info.final_states.contains(state))
+    fn state_set(&self) -> String {
+        format!("{{{}}}", self.state_names().join(", "))
     }
 
-    fn find_symbol(&self, c: char) -> Option<&'static str> {
+    fn find_symbol(&self, c: char) -> Option<SymbolId> {
         // Find the symbol in the alphabet that matches the character
         self.info
-            .alphabet
-            .iter()
-            .find(|&&s| s.len() == 1 && s != "ε" && s.chars().next().unwrap() == c)
-            .copied()
+            .table
+            .char_symbol(c)
+            .filter(|&s| s != SymbolId::EPSILON && self.info.alphabet.contains(&s))
     }
 
-    fn get_next_states(&self, symbol: &'static str) -> HashSet<&'static str> {
+    fn get_next_states(&self, symbol: SymbolId) -> HashSet<StateId> {
         let mut next_states = HashSet::new();
 
         // For each current state, find transitions with the given symbol
@@ -213,33 +307,68 @@ impl Machine {
             }
         }
 
-        Self::compute_epsilon_closure(&self.info, next_states)
+        self.info.epsilon_closure(next_states)
     }
+}
 
-    fn compute_epsilon_closure(
-        info: &Info,
-        states: HashSet<&'static str>,
-    ) -> HashSet<&'static str> {
-        let mut closure = states.clone();
-        let mut stack = states.into_iter().collect::<Vec<&'static str>>();
+impl super::Machine for Machine {
+    fn build(machine: PartialMachineInfo, src: &str) -> miette::Result<Self> {
+        Ok(Self::new(Info::new(machine, src)?))
+    }
 
-        while let Some(state) = stack.pop() {
-            let key = TransitionFrom {
-                initial: state,
-                with_symbol: "ε",
-            };
+    fn clone_box(&self) -> Box<dyn super::Machine> {
+        Box::new(self.clone())
+    }
 
-            if let Some(transitions) = info.transitions.get(&key) {
-                for transition in transitions {
-                    let target_state = transition.0;
-                    if !closure.contains(target_state) {
-                        closure.insert(target_state);
-                        stack.push(target_state);
-                    }
-                }
+    fn reset(&mut self, input: &str) {
+        self.current_states = Self::initial_states(&self.info);
+        self.input = input.chars().collect();
+        self.position = 0;
+    }
+
+    fn step(&mut self) -> Step {
+        let Some(&c) = self.input.get(self.position) else {
+            // check if any of the current states are final states
+            let accepted = self
+                .current_states
+                .iter()
+                .any(|state| self.info.final_states.contains(state));
+
+            return if accepted {
+                Step::Halt(Verdict::Accepted)
+            } else {
+                Step::Halt(Verdict::Rejected(Rejection::NotAccepting {
+                    state: self.state_set(),
+                }))
+            };
+        };
+
+        let symbol = self.find_symbol(c);
+
+        if let Some(symbol) = symbol {
+            let next_states = self.get_next_states(symbol);
+            if next_states.is_empty() {
+                return Step::Halt(Verdict::Rejected(Rejection::NoTransition {
+                    state: self.state_set(),
+                    symbol: self.info.table.symbol_name(symbol).to_string(),
+                    stack_top: None,
+                }));
             }
+
+            self.current_states = next_states;
+
+            self.position += 1;
+            Step::Continue
+        } else {
+            // Symbol not in alphabet
+            Step::Halt(Verdict::Rejected(Rejection::UnknownSymbol { symbol: c }))
         }
+    }
 
-        closure
+    fn configuration(&self) -> Configuration {
+        Configuration::Nfa {
+            states: self.state_names(),
+            remaining_input: self.input[self.position..].iter().collect(),
+        }
     }
 }
@@ -1,30 +1,179 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    rc::Rc,
+};
 
-use miette::Diagnostic;
+use miette::{Diagnostic, SourceSpan};
 use thiserror::Error;
 
+use crate::diagram::Diagram;
 use crate::parser::{ParserError, PartialMachineInfo, StackTransition};
 
+use super::{
+    Configuration, Rejection, Step, Verdict,
+    symbols::{StateId, SymbolId, SymbolTable},
+};
+
+/// What a transition expects on top of the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StackTop {
+    /// The stack isn't looked at, it may even be empty
+    Ignored,
+    /// Any symbol, written `*`: the stack only has to be non-empty
+    Any,
+    Symbol(SymbolId),
+}
+
+impl StackTop {
+    /// The expected top as written in a machine file, if anything is expected
+    fn name(self, table: &SymbolTable) -> Option<&str> {
+        match self {
+            StackTop::Ignored => None,
+            StackTop::Any => Some("*"),
+            StackTop::Symbol(symbol) => Some(table.symbol_name(symbol)),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct PDATransitionFrom {
-    initial: &'static str,
-    with_symbol: &'static str,
-    stack_top: Option<&'static str>,
+    pub initial: StateId,
+    pub with_symbol: SymbolId,
+    pub stack_top: StackTop,
 }
 
 #[derive(Debug, Clone)]
 pub struct PDATransitionTo {
-    state: &'static str,
-    stack_action: StackAction,
+    pub state: StateId,
+    pub stack_action: StackAction,
 }
 
 #[derive(Debug, Clone)]
 pub enum StackAction {
-    Push(&'static str),
+    /// Pushes the symbols, the first one ending up on top
+    Push(Vec<SymbolId>),
+    /// Pops the top of the stack and pushes the symbols in its place, in one move
+    Replace(Vec<SymbolId>),
     Pop,
     NoOp,
 }
 
+impl StackAction {
+    /// Symbols the action puts on the stack, top first
+    fn pushed(&self) -> &[SymbolId] {
+        match self {
+            StackAction::Push(symbols) | StackAction::Replace(symbols) => symbols,
+            StackAction::Pop | StackAction::NoOp => &[],
+        }
+    }
+
+    /// The action as written in a machine file, lists of several symbols in brackets
+    fn name(&self, table: &SymbolTable) -> String {
+        let symbols = |symbols: &[SymbolId]| match symbols {
+            [symbol] => table.symbol_name(*symbol).to_string(),
+            symbols => {
+                let names: Vec<&str> = symbols
+                    .iter()
+                    .map(|&symbol| table.symbol_name(symbol))
+                    .collect();
+                format!("[{}]", names.join(","))
+            }
+        };
+
+        match self {
+            StackAction::Push(pushed) => format!("PUSH:{}", symbols(pushed)),
+            StackAction::Replace(pushed) => format!("REPLACE:{}", symbols(pushed)),
+            StackAction::Pop => "POP".to_string(),
+            StackAction::NoOp => "NOOP".to_string(),
+        }
+    }
+}
+
+/// When a PDA accepts, once it has read its whole input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Acceptance {
+    /// In a final state
+    #[default]
+    FinalState,
+    /// With an empty stack, whatever the state
+    EmptyStack,
+    /// In a final state with an empty stack
+    Both,
+}
+
+impl Acceptance {
+    /// Reads the mode declared in the `[acceptance]` section, if there is one
+    pub fn declared(machine: &PartialMachineInfo, src: &str) -> miette::Result<Option<Self>> {
+        let Some(token) = &machine.acceptance else {
+            return Ok(None);
+        };
+
+        let acceptance = match token.src(src).to_ascii_lowercase().as_str() {
+            "final_state" => Acceptance::FinalState,
+            "empty_stack" => Acceptance::EmptyStack,
+            "both" => Acceptance::Both,
+            _ => return Err(ParserError::UnknownAcceptanceMode { at: token.span() }.into()),
+        };
+
+        Ok(Some(acceptance))
+    }
+
+    fn needs_final_state(self) -> bool {
+        matches!(self, Acceptance::FinalState | Acceptance::Both)
+    }
+
+    fn needs_empty_stack(self) -> bool {
+        matches!(self, Acceptance::EmptyStack | Acceptance::Both)
+    }
+}
+
+impl fmt::Display for Acceptance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Acceptance::FinalState => write!(f, "final_state"),
+            Acceptance::EmptyStack => write!(f, "empty_stack"),
+            Acceptance::Both => write!(f, "both"),
+        }
+    }
+}
+
+/// What happens when a PDA pops its stack while it is empty
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StackSemantics {
+    /// The stack stays empty and the branch carries on
+    #[default]
+    Lenient,
+    /// The branch dies
+    Strict,
+}
+
+impl StackSemantics {
+    /// Reads the semantics declared in the `[stack_semantics]` section, if there is one
+    pub fn declared(machine: &PartialMachineInfo, src: &str) -> miette::Result<Option<Self>> {
+        let Some(token) = &machine.stack_semantics else {
+            return Ok(None);
+        };
+
+        let semantics = match token.src(src).to_ascii_lowercase().as_str() {
+            "lenient" => StackSemantics::Lenient,
+            "strict" => StackSemantics::Strict,
+            _ => return Err(ParserError::UnknownStackSemantics { at: token.span() }.into()),
+        };
+
+        Ok(Some(semantics))
+    }
+}
+
+impl fmt::Display for StackSemantics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackSemantics::Lenient => write!(f, "lenient"),
+            StackSemantics::Strict => write!(f, "strict"),
+        }
+    }
+}
+
 #[derive(Error, Diagnostic, Debug)]
 pub enum PDAError {
     #[error("PDA must have a stack alphabet and stack information in each transition")]
@@ -35,77 +184,97 @@ pub enum PDAError {
 
 #[derive(Debug, Clone)]
 pub struct Info {
-    pub alphabet: HashSet<&'static str>,
+    pub table: SymbolTable,
+    pub alphabet: HashSet<SymbolId>,
     pub transitions: HashMap<PDATransitionFrom, Vec<PDATransitionTo>>,
-    pub start_state: &'static str,
-    pub final_states: HashSet<&'static str>,
-    pub start_stack_symbol: Option<&'static str>,
+    pub start_state: StateId,
+    pub final_states: HashSet<StateId>,
+    pub stack_alphabet: HashSet<SymbolId>,
+    pub start_stack_symbol: Option<SymbolId>,
+    pub acceptance: Acceptance,
+    pub stack_semantics: StackSemantics,
 }
 
 impl Info {
-    pub fn new(machine: PartialMachineInfo, src: &'static str) -> miette::Result<Self> {
+    pub fn new(machine: PartialMachineInfo, src: &str) -> miette::Result<Self> {
         // Check for tape operations which are not allowed in PDA
         if machine.tape_alphabet.is_some() || machine.blank_symbol.is_some() {
             return Err(PDAError::TapeOperationsNotAllowed.into());
         }
 
-        let mut states = HashSet::new();
+        let mut table = SymbolTable::new();
         let mut alphabet = HashSet::new();
         let mut stack_alphabet = HashSet::new();
         let mut transitions = HashMap::new();
         let mut final_states = HashSet::new();
         let mut start_stack_symbol = None;
+        let acceptance = Acceptance::declared(&machine, src)?.unwrap_or_default();
+        let stack_semantics = StackSemantics::declared(&machine, src)?.unwrap_or_default();
 
         for state in &machine.states {
-            states.insert(state.src(src));
+            table.intern_state(state.src(src));
         }
 
         for symbol in &machine.alphabet {
-            alphabet.insert(symbol.src(src));
+            alphabet.insert(table.intern_symbol(symbol.src(src)));
         }
 
-        alphabet.insert("ε");
+        alphabet.insert(SymbolId::EPSILON);
 
         if let Some(stack_symbs) = &machine.stack_alphabet {
             for symbol in stack_symbs {
-                stack_alphabet.insert(symbol.src(src));
+                stack_alphabet.insert(table.intern_symbol(symbol.src(src)));
             }
         } else {
             return Err(PDAError::StackOperationsRequired.into());
         }
 
-        if let Some(start_stack) = &machine.start_stack {
-            let symbol = start_stack.src(src);
-            if !stack_alphabet.is_empty() && !stack_alphabet.contains(symbol) {
-                return Err(ParserError::UnknownAlphabetSymbol {
-                    at: start_stack.span(),
-                }
-                .into());
+        // An empty stack alphabet accepts any stack symbol, and is made of the ones used
+        let any_stack_symbol = stack_alphabet.is_empty();
+        // `*` is the wildcard, unless it is a stack symbol of its own
+        let star_is_symbol = machine
+            .stack_alphabet
+            .iter()
+            .flatten()
+            .any(|symbol| symbol.src(src) == "*");
+        let mut stack_symbol = |table: &mut SymbolTable, name: &str, at: SourceSpan| {
+            if any_stack_symbol {
+                let symbol = table.intern_symbol(name);
+                stack_alphabet.insert(symbol);
+                return Ok(symbol);
             }
 
-            start_stack_symbol = Some(symbol);
+            table
+                .symbol(name)
+                .filter(|symbol| stack_alphabet.contains(symbol))
+                .ok_or(ParserError::UnknownAlphabetSymbol { at })
+        };
+
+        if let Some(start_stack) = &machine.start_stack {
+            start_stack_symbol = Some(stack_symbol(
+                &mut table,
+                start_stack.src(src),
+                start_stack.span(),
+            )?);
         }
 
         for final_state in &machine.final_states {
-            let state_str = final_state.src(src);
-
-            if !states.contains(state_str) {
+            let Some(state) = table.state(final_state.src(src)) else {
                 return Err(ParserError::UnknownState {
                     at: final_state.span(),
                 }
                 .into());
-            }
+            };
 
-            final_states.insert(state_str);
+            final_states.insert(state);
         }
 
-        let start_state = machine.start_state.src(src);
-        if !states.contains(start_state) {
+        let Some(start_state) = table.state(machine.start_state.src(src)) else {
             return Err(ParserError::UnknownState {
                 at: machine.start_state.span(),
             }
             .into());
-        }
+        };
 
         // Process transitions
         for transition in &machine.transitions {
@@ -114,46 +283,39 @@ impl Info {
                 return Err(PDAError::StackOperationsRequired.into());
             }
 
-            let from_state = transition.from.initial.src(src);
-            let symbol = transition.from.with_symbol.src(src);
-            let to_state = transition.to.0.src(src);
-
             // Validate transition states and symbols
-            if !states.contains(from_state) {
+            let Some(from_state) = table.state(transition.from.initial.src(src)) else {
                 return Err(ParserError::UnknownState {
                     at: transition.from.initial.span(),
                 }
                 .into());
-            }
+            };
 
-            if !states.contains(to_state) {
+            let Some(to_state) = table.state(transition.to.0.src(src)) else {
                 return Err(ParserError::UnknownState {
                     at: transition.to.0.span(),
                 }
                 .into());
-            }
+            };
 
-            if !alphabet.contains(symbol) && symbol != "ε" {
+            let Some(symbol) = table
+                .symbol(transition.from.with_symbol.src(src))
+                .filter(|symbol| alphabet.contains(symbol))
+            else {
                 return Err(ParserError::UnknownAlphabetSymbol {
                     at: transition.from.with_symbol.span(),
                 }
                 .into());
-            }
+            };
 
             // Handle stack symbols in the transition
-            let stack_top = if let Some(stack_symbol) = &transition.from.with_stack_symbol {
-                let stack_sym_str = stack_symbol.src(src);
-                if !stack_alphabet.is_empty() && !stack_alphabet.contains(stack_sym_str) {
-                    return Err(ParserError::UnknownAlphabetSymbol {
-                        at: stack_symbol.span(),
-                    }
-                    .into());
+            let stack_top = match &transition.from.with_stack_symbol {
+                Some(token) if token.src(src) == "*" && !star_is_symbol => StackTop::Any,
+                Some(token) => {
+                    StackTop::Symbol(stack_symbol(&mut table, token.src(src), token.span())?)
                 }
-
-                Some(stack_sym_str)
-            } else {
                 // No specific stack symbol required
-                None
+                None => StackTop::Ignored,
             };
 
             // Check for tape operations (direction) which are not allowed in PDA
@@ -164,14 +326,23 @@ impl Info {
             // Extract stack action from transition
             let stack_action = match &transition.to.1 {
                 Some(stack_trans) => match stack_trans {
-                    StackTransition::Push(_, symbol) => {
-                        let symbol_str = symbol.src(src);
-                        if !stack_alphabet.is_empty() && !stack_alphabet.contains(symbol_str) {
-                            return Err(
-                                ParserError::UnknownAlphabetSymbol { at: symbol.span() }.into()
-                            );
+                    StackTransition::Push(_, tokens) | StackTransition::Replace(_, tokens) => {
+                        // Several symbols are always listed in brackets, `BCA` is one
+                        let mut symbols = vec![];
+                        for token in tokens {
+                            if token.src(src) != "ε" {
+                                symbols.push(stack_symbol(
+                                    &mut table,
+                                    token.src(src),
+                                    token.span(),
+                                )?);
+                            }
+                        }
+
+                        match stack_trans {
+                            StackTransition::Push(..) => StackAction::Push(symbols),
+                            _ => StackAction::Replace(symbols),
                         }
-                        StackAction::Push(symbol_str)
                     }
                     StackTransition::Pop(_) => StackAction::Pop,
                     StackTransition::NoOp(_) => StackAction::NoOp,
@@ -199,183 +370,1037 @@ impl Info {
         }
 
         Ok(Info {
+            table,
             alphabet,
             transitions,
             start_state,
             final_states,
+            stack_alphabet,
             start_stack_symbol,
+            acceptance,
+            stack_semantics,
         })
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct Machine {
-    info: Info,
-    current_state: &'static str,
-    stack: VecDeque<&'static str>,
-}
+    fn add_transition(&mut self, from: PDATransitionFrom, to: PDATransitionTo) {
+        self.transitions.entry(from).or_default().push(to);
+    }
 
-impl Machine {
-    pub fn new(info: Info) -> Self {
-        let mut stack = VecDeque::new();
+    /// PDA accepting the same language under another acceptance mode. A fresh `⊥` is put
+    /// at the bottom of the stack, under the old start symbol, so that the stack only
+    /// empties when the new PDA decides to. Every configuration the old PDA accepts in
+    /// then moves on epsilon to a new state: `accept`, the only final state, or `drain`,
+    /// which pops the whole stack
+    pub fn converted(&self, acceptance: Acceptance) -> Info {
+        if acceptance == self.acceptance {
+            return self.clone();
+        }
+
+        let mut info = self.clone();
+        let mut accepting: Vec<StateId> = if self.acceptance.needs_final_state() {
+            self.final_states.iter().copied().collect()
+        } else {
+            self.table.states().collect()
+        };
+        accepting.sort_unstable();
+
+        let bottom = info.table.fresh_symbol("⊥");
+        info.stack_alphabet.insert(bottom);
+        info.start_stack_symbol = Some(bottom);
+
+        let mut old_stack_symbols: Vec<SymbolId> = self.stack_alphabet.iter().copied().collect();
+        old_stack_symbols.sort_unstable();
+
+        // The old moves must see the marker as the empty stack, so the ones that would pop
+        // it are spelled out for every old symbol instead
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by_key(|(from, _)| (from.initial, from.with_symbol, from.stack_top));
+        info.transitions = HashMap::new();
+        for (from, targets) in transitions {
+            for to in targets {
+                let pops = matches!(to.stack_action, StackAction::Pop | StackAction::Replace(_));
+                let may_see_marker = match from.stack_top {
+                    StackTop::Ignored => pops,
+                    StackTop::Any => true,
+                    StackTop::Symbol(_) => false,
+                };
+                if !may_see_marker {
+                    info.add_transition(from.clone(), to.clone());
+                    continue;
+                }
+
+                for &symbol in &old_stack_symbols {
+                    let from = PDATransitionFrom {
+                        stack_top: StackTop::Symbol(symbol),
+                        ..from.clone()
+                    };
+                    info.add_transition(from, to.clone());
+                }
 
-        if let Some(start_symbol) = info.start_stack_symbol {
-            stack.push_back(start_symbol);
+                // Popping the empty stack leaves it empty, when the branch survives it
+                if from.stack_top == StackTop::Ignored
+                    && self.stack_semantics == StackSemantics::Lenient
+                {
+                    let pushed = to.stack_action.pushed();
+                    let from = PDATransitionFrom {
+                        stack_top: StackTop::Symbol(bottom),
+                        ..from.clone()
+                    };
+                    let to = PDATransitionTo {
+                        state: to.state,
+                        stack_action: if pushed.is_empty() {
+                            StackAction::NoOp
+                        } else {
+                            StackAction::Push(pushed.to_vec())
+                        },
+                    };
+                    info.add_transition(from, to);
+                }
+            }
         }
 
-        Self {
-            current_state: info.start_state,
-            info,
-            stack,
+        if let Some(symbol) = self.start_stack_symbol {
+            let start = info.table.fresh_state("start");
+            info.start_state = start;
+            info.add_transition(
+                PDATransitionFrom {
+                    initial: start,
+                    with_symbol: SymbolId::EPSILON,
+                    stack_top: StackTop::Ignored,
+                },
+                PDATransitionTo {
+                    state: self.start_state,
+                    stack_action: StackAction::Push(vec![symbol]),
+                },
+            );
+        }
+
+        let end = match acceptance {
+            Acceptance::FinalState => info.table.fresh_state("accept"),
+            Acceptance::EmptyStack | Acceptance::Both => {
+                let drain = info.table.fresh_state("drain");
+                info.add_transition(
+                    PDATransitionFrom {
+                        initial: drain,
+                        with_symbol: SymbolId::EPSILON,
+                        stack_top: StackTop::Any,
+                    },
+                    PDATransitionTo {
+                        state: drain,
+                        stack_action: StackAction::Pop,
+                    },
+                );
+                drain
+            }
+        };
+
+        // The old stack is empty once only the marker is left
+        let stack_top = if self.acceptance.needs_empty_stack() {
+            StackTop::Symbol(bottom)
+        } else {
+            StackTop::Ignored
+        };
+        for state in accepting {
+            info.add_transition(
+                PDATransitionFrom {
+                    initial: state,
+                    with_symbol: SymbolId::EPSILON,
+                    stack_top,
+                },
+                PDATransitionTo {
+                    state: end,
+                    stack_action: StackAction::NoOp,
+                },
+            );
+        }
+
+        info.final_states = match acceptance {
+            Acceptance::EmptyStack => HashSet::new(),
+            Acceptance::FinalState | Acceptance::Both => HashSet::from([end]),
+        };
+        info.acceptance = acceptance;
+        info
+    }
+
+    /// State diagram of the PDA, edges are labelled `input, top / action`
+    pub fn diagram(&self) -> Diagram {
+        let mut diagram = Diagram::new(&self.table, self.start_state, &self.final_states);
+
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by_key(|(from, _)| (from.initial, from.with_symbol, from.stack_top));
+
+        for (from, targets) in transitions {
+            let read = match from.stack_top.name(&self.table) {
+                Some(top) => format!("{}, {top}", self.table.symbol_name(from.with_symbol)),
+                None => self.table.symbol_name(from.with_symbol).to_string(),
+            };
+
+            for to in targets {
+                let action = to.stack_action.name(&self.table);
+                diagram.add_edge(from.initial, to.state, format!("{read} / {action}"));
+            }
         }
+
+        diagram
     }
+}
+
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = &self.table;
+        let names = |symbols: &HashSet<SymbolId>| {
+            let mut symbols: Vec<SymbolId> = symbols
+                .iter()
+                .copied()
+                .filter(|&symbol| symbol != SymbolId::EPSILON)
+                .collect();
+            symbols.sort_unstable();
+            symbols
+                .iter()
+                .map(|&symbol| table.symbol_name(symbol))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let states: Vec<&str> = table
+            .states()
+            .map(|state| table.state_name(state))
+            .collect();
+        writeln!(f, "[states]\n{}\n", states.join(", "))?;
+        writeln!(f, "[alphabet]\n{}\n", names(&self.alphabet))?;
+        writeln!(f, "[stack_alphabet]\n{}\n", names(&self.stack_alphabet))?;
+        writeln!(f, "[initial]\n{}\n", table.state_name(self.start_state))?;
+        if let Some(symbol) = self.start_stack_symbol {
+            writeln!(f, "[start_stack]\n{}\n", table.symbol_name(symbol))?;
+        }
+
+        let mut final_states: Vec<StateId> = self.final_states.iter().copied().collect();
+        final_states.sort_unstable();
+        let final_states: Vec<&str> = final_states
+            .iter()
+            .map(|&state| table.state_name(state))
+            .collect();
+        if final_states.is_empty() {
+            writeln!(f, "[final]\n")?;
+        } else {
+            writeln!(f, "[final]\n{}\n", final_states.join(", "))?;
+        }
 
-    pub fn run(mut self, input: &str) -> bool {
-        // Process each character in the input using a deterministic approach
-        // (taking the first valid transition)
-        let input_chars: Vec<char> = input.chars().collect();
-        let mut position = 0;
+        if self.acceptance != Acceptance::FinalState {
+            writeln!(f, "[acceptance]\n{}\n", self.acceptance)?;
+        }
 
-        // First, try to process any epsilon transitions before consuming input
-        while self.make_transition("ε", self.stack.back().copied()) {
-            // Continue making epsilon transitions until none are left
+        if self.stack_semantics != StackSemantics::Lenient {
+            writeln!(f, "[stack_semantics]\n{}\n", self.stack_semantics)?;
         }
 
-        // Process until we've consumed all input and can't make any more epsilon transitions
-        while position < input_chars.len() || self.can_make_epsilon_transition() {
-            let current_symbol = if position < input_chars.len() {
-                self.find_symbol(input_chars[position])
+        // A declared `*` reads back as that symbol, so the wildcard is spelled out as
+        // every stack symbol instead
+        let star_is_symbol = table
+            .symbol("*")
+            .is_some_and(|symbol| self.stack_alphabet.contains(&symbol));
+        let mut stack_symbols: Vec<SymbolId> = self.stack_alphabet.iter().copied().collect();
+        stack_symbols.sort_unstable();
+
+        writeln!(f, "[transitions]")?;
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by_key(|(from, _)| (from.initial, from.with_symbol, from.stack_top));
+        for (from, targets) in transitions {
+            let tops = if from.stack_top == StackTop::Any && star_is_symbol {
+                stack_symbols
+                    .iter()
+                    .map(|&symbol| StackTop::Symbol(symbol))
+                    .collect()
             } else {
-                // If we've consumed all input but still have states to process,
-                // try epsilon transitions
-                Some("ε")
+                vec![from.stack_top]
             };
 
-            if let Some(symbol) = current_symbol {
-                // Get the stack top if available
-                let stack_top = self.stack.back().copied();
+            for top in tops {
+                let read = match top.name(table) {
+                    Some(top) => format!("{}, {top}", table.symbol_name(from.with_symbol)),
+                    None => table.symbol_name(from.with_symbol).to_string(),
+                };
 
-                // Always try epsilon transitions first
-                let mut made_transition = false;
-                if symbol != "ε" {
-                    while self.make_transition("ε", self.stack.back().copied()) {
-                        made_transition = true;
-                    }
+                for to in targets {
+                    let action = to.stack_action.name(table);
+                    writeln!(
+                        f,
+                        "{}({read}) => ({}, {action})",
+                        table.state_name(from.initial),
+                        table.state_name(to.state)
+                    )?;
                 }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// One branch of the search: a configuration the PDA can be in
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Branch {
+    state: StateId,
+    /// Number of input characters consumed
+    position: usize,
+    /// Bottom first
+    stack: Vec<SymbolId>,
+}
+
+/// What the search is looking for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Goal {
+    /// A configuration the PDA accepts in
+    Accept,
+    /// Any configuration with this many input characters consumed
+    Reach(usize),
+}
+
+/// Where a branch is and what is on top of its stack: input position, state and stack
+/// symbol, `None` standing for the bottom of the stack
+type Head = (usize, StateId, Option<SymbolId>);
+
+/// Where a branch can be once the symbol on top of its stack is popped: input position
+/// and state, or no state when the goal is reached before the symbol comes off
+type Exit = (usize, Option<StateId>);
+
+/// Symbols a move left on the stack, top first, of which the ones before `next` are
+/// popped already, on the way to an exit of `origin`
+#[derive(Clone)]
+struct Chain {
+    origin: Head,
+    symbols: Rc<[Option<SymbolId>]>,
+    next: usize,
+}
+
+enum Work {
+    Start(Head),
+    Exit(Head, Exit),
+    Continue(Chain, Exit),
+}
+
+/// Every exit of every head the search comes across, like the variables `[p X q]` of the
+/// grammar built out of a PDA. There are finitely many of both for a given input, so
+/// working them out terminates even when epsilon moves can grow the stack forever, and
+/// a branch is only worth exploring when chaining the exits of its stack symbols, top
+/// to bottom, reaches the goal
+struct Exits<'a> {
+    machine: &'a Machine,
+    goal: Goal,
+    exits: HashMap<Head, HashSet<Exit>>,
+    /// Chains to carry on with once the head gets another exit
+    waiting: HashMap<Head, Vec<Chain>>,
+    work: Vec<Work>,
+}
+
+impl<'a> Exits<'a> {
+    fn new(machine: &'a Machine, goal: Goal) -> Self {
+        Self {
+            machine,
+            goal,
+            exits: HashMap::new(),
+            waiting: HashMap::new(),
+            work: vec![],
+        }
+    }
 
-                // Then try to make a transition with the current input symbol
-                if self.make_transition(symbol, self.stack.back().copied()) {
-                    made_transition = true;
-                    if position < input_chars.len() && symbol != "ε" {
-                        // Move to next input character
-                        position += 1;
+    fn reached(&self, (position, state, top): Head) -> bool {
+        let info = &self.machine.info;
+        match self.goal {
+            Goal::Accept => {
+                position == self.machine.input.len()
+                    && (!info.acceptance.needs_final_state() || info.final_states.contains(&state))
+                    && (!info.acceptance.needs_empty_stack() || top.is_none())
+            }
+            Goal::Reach(goal) => position == goal,
+        }
+    }
+
+    /// Exits of `head`, worked out along with those of every head it leads to
+    fn of(&mut self, head: Head) -> Vec<Exit> {
+        self.work.push(Work::Start(head));
+
+        while let Some(work) = self.work.pop() {
+            match work {
+                Work::Start(head) => {
+                    if self.exits.contains_key(&head) {
+                        continue;
+                    }
+                    self.exits.insert(head, HashSet::new());
+
+                    let (position, _, _) = head;
+                    if self.reached(head) {
+                        self.work.push(Work::Exit(head, (position, None)));
+                    }
+                    for (consumed, state, symbols) in self.machine.head_moves(head) {
+                        let chain = Chain {
+                            origin: head,
+                            symbols: symbols.into(),
+                            next: 0,
+                        };
+                        self.work
+                            .push(Work::Continue(chain, (position + consumed, Some(state))));
+                    }
+                }
+                Work::Exit(head, exit) => {
+                    if self.exits.entry(head).or_default().insert(exit) {
+                        for chain in self.waiting.get(&head).into_iter().flatten() {
+                            self.work.push(Work::Continue(chain.clone(), exit));
+                        }
                     }
                 }
+                Work::Continue(chain, (position, state)) => {
+                    let (Some(state), Some(&top)) = (state, chain.symbols.get(chain.next)) else {
+                        // Everything the move pushed is popped, or the goal is reached
+                        self.work.push(Work::Exit(chain.origin, (position, state)));
+                        continue;
+                    };
 
-                if !made_transition {
-                    // No valid transition found for the current state, symbol, and stack
-                    if symbol == "ε" && position >= input_chars.len() {
-                        // If we can't make any more epsilon transitions after consuming all input,
-                        // we need to determine if we're in an accepting state
-                        break;
-                    } else {
-                        println!(
-                            "No valid transition found for state '{}', symbol '{}', stack top '{:?}'. Rejecting input.",
-                            self.current_state, symbol, stack_top
-                        );
-                        return false;
+                    let head = (position, state, top);
+                    let chain = Chain {
+                        next: chain.next + 1,
+                        ..chain
+                    };
+                    for &exit in self.exits.get(&head).into_iter().flatten() {
+                        self.work.push(Work::Continue(chain.clone(), exit));
                     }
+                    self.waiting.entry(head).or_default().push(chain);
+                    self.work.push(Work::Start(head));
                 }
-            } else {
-                // Symbol not in alphabet
-                println!(
-                    "Symbol '{}' not in alphabet. Rejecting input.",
-                    input_chars[position]
-                );
-                return false;
             }
         }
 
-        // Accept if we're in a final state
-        let is_accepted = self.info.final_states.contains(self.current_state);
-        is_accepted
+        self.exits[&head].iter().copied().collect()
     }
 
-    fn find_symbol(&self, c: char) -> Option<&'static str> {
-        self.info
-            .alphabet
-            .iter()
-            .find(|&&s| s.len() == 1 && s != "ε" && s.chars().next().unwrap() == c)
-            .copied()
+    /// Whether `branch` can still get to the goal
+    fn leads_to_goal(&mut self, branch: &Branch) -> bool {
+        let mut exits = HashSet::from([(branch.position, Some(branch.state))]);
+        let stack = branch.stack.iter().rev().map(|&symbol| Some(symbol));
+
+        for top in stack.chain([None]) {
+            let mut next = HashSet::new();
+            for (position, state) in exits {
+                let Some(state) = state else {
+                    return true;
+                };
+                next.extend(self.of((position, state, top)));
+            }
+            exits = next;
+        }
+
+        // Nothing pops the bottom of the stack, so only the goal gets past it
+        !exits.is_empty()
     }
 
-    fn can_make_epsilon_transition(&self) -> bool {
-        let stack_top = self.stack.back().copied();
+    /// Furthest input position of the heads explored so far
+    fn furthest(&self) -> usize {
+        self.exits
+            .keys()
+            .map(|&(position, _, _)| position)
+            .max()
+            .unwrap_or(0)
+    }
 
-        for (key, _) in &self.info.transitions {
-            if key.initial == self.current_state && key.with_symbol == "ε" {
-                if key.stack_top.is_none() || key.stack_top == stack_top {
-                    return true;
+    /// Breadth first search from `start`, which must lead to the goal, only following
+    /// branches that still do. Returns the shortest run found, in order
+    fn path(&mut self, start: Branch) -> Vec<Branch> {
+        // Every branch queued, with the index of the one it was reached from
+        let mut explored: Vec<(Branch, Option<usize>)> = vec![(start.clone(), None)];
+        let mut visited = HashSet::from([start]);
+
+        let mut index = 0;
+        loop {
+            let branch = &explored[index].0;
+            let done = match self.goal {
+                Goal::Accept => self.machine.accepts(branch),
+                Goal::Reach(goal) => branch.position == goal,
+            };
+            if done {
+                break;
+            }
+
+            for next in self.machine.successors(branch) {
+                if visited.insert(next.clone()) && self.leads_to_goal(&next) {
+                    explored.push((next, Some(index)));
                 }
             }
+            index += 1;
         }
 
-        false
+        let mut path = vec![];
+        let mut current = Some(index);
+        while let Some(index) = current {
+            let (branch, parent) = &explored[index];
+            path.push(branch.clone());
+            current = *parent;
+        }
+        path.reverse();
+        path
     }
+}
 
-    fn make_transition(&mut self, symbol: &'static str, stack_top: Option<&'static str>) -> bool {
-        let mut matching_transitions = Vec::new();
+/// Runs the PDA nondeterministically. Loading an input searches breadth first through
+/// the configurations for an accepting one, skipping the branches that can't get there
+/// any more, and stepping then follows the run that was found. When no branch accepts,
+/// the run followed is one that read as much of the input as any could
+#[derive(Debug, Clone)]
+pub struct Machine {
+    info: Info,
+    input: Vec<char>,
+    /// Run found by the search, starting with the initial configuration
+    path: Vec<Branch>,
+    /// Index of the current configuration in `path`
+    current: usize,
+    /// How the run ends
+    verdict: Verdict,
+}
 
-        // First, try to find transitions that match the specific stack top
-        if let Some(top) = stack_top {
-            for (key, transitions) in &self.info.transitions {
-                if key.initial == self.current_state
-                    && key.with_symbol == symbol
-                    && key.stack_top == Some(top)
-                {
-                    matching_transitions.extend(transitions.iter().cloned());
-                }
+impl Machine {
+    pub fn new(info: Info) -> Self {
+        let start = Self::initial_branch(&info);
+        let mut machine = Self {
+            info,
+            input: vec![],
+            path: vec![start],
+            current: 0,
+            verdict: Verdict::Accepted,
+        };
+        super::Machine::reset(&mut machine, "");
+        machine
+    }
+
+    fn initial_branch(info: &Info) -> Branch {
+        Branch {
+            state: info.start_state,
+            position: 0,
+            stack: info.start_stack_symbol.into_iter().collect(),
+        }
+    }
+
+    fn find_symbol(&self, c: char) -> Option<SymbolId> {
+        self.info
+            .table
+            .char_symbol(c)
+            .filter(|&s| s != SymbolId::EPSILON && self.info.alphabet.contains(&s))
+    }
+
+    /// Moves out of `state` on `symbol`: those expecting `top`, those taking any top, and
+    /// those that don't look at the stack
+    fn moves(
+        &self,
+        state: StateId,
+        top: Option<SymbolId>,
+        symbol: SymbolId,
+    ) -> Vec<&PDATransitionTo> {
+        let stack_tops = match top {
+            Some(top) => vec![StackTop::Symbol(top), StackTop::Any, StackTop::Ignored],
+            None => vec![StackTop::Ignored],
+        };
+
+        let mut moves = vec![];
+        for stack_top in stack_tops {
+            let key = PDATransitionFrom {
+                initial: state,
+                with_symbol: symbol,
+                stack_top,
+            };
+
+            if let Some(targets) = self.info.transitions.get(&key) {
+                moves.extend(targets);
             }
         }
+        moves
+    }
 
-        // If no specific match, look for transitions with no stack top requirement
-        if matching_transitions.is_empty() {
-            for (key, transitions) in &self.info.transitions {
-                if key.initial == self.current_state
-                    && key.with_symbol == symbol
-                    && key.stack_top.is_none()
-                {
-                    matching_transitions.extend(transitions.iter().cloned());
+    /// What `action` leaves in place of `top`, top first, or `None` when it pops the empty
+    /// stack under strict semantics. `None` in the list is the bottom of the stack
+    fn replacement(
+        &self,
+        top: Option<SymbolId>,
+        action: &StackAction,
+    ) -> Option<Vec<Option<SymbolId>>> {
+        let pushed = action.pushed().iter().map(|&symbol| Some(symbol));
+        match (action, top) {
+            (StackAction::Push(_), _) => Some(pushed.chain([top]).collect()),
+            (StackAction::NoOp, _) => Some(vec![top]),
+            (StackAction::Pop | StackAction::Replace(_), Some(_)) => Some(pushed.collect()),
+            (StackAction::Pop | StackAction::Replace(_), None) => match self.info.stack_semantics {
+                StackSemantics::Lenient => Some(pushed.chain([None]).collect()),
+                StackSemantics::Strict => None,
+            },
+        }
+    }
+
+    /// Moves out of `head`, as the number of input characters they consume, the state
+    /// they go to and what they leave in place of the top of the stack
+    fn head_moves(
+        &self,
+        (position, state, top): Head,
+    ) -> Vec<(usize, StateId, Vec<Option<SymbolId>>)> {
+        let mut symbols = vec![(SymbolId::EPSILON, 0)];
+        if let Some(symbol) = self.input.get(position).and_then(|&c| self.find_symbol(c)) {
+            symbols.push((symbol, 1));
+        }
+
+        let mut moves = vec![];
+        for (symbol, consumed) in symbols {
+            for to in self.moves(state, top, symbol) {
+                if let Some(replacement) = self.replacement(top, &to.stack_action) {
+                    moves.push((consumed, to.state, replacement));
                 }
             }
         }
+        moves
+    }
+
+    /// Branch `to` leads to, or `None` when it pops the empty stack under strict
+    /// semantics
+    fn apply(&self, branch: &Branch, to: &PDATransitionTo, consumed: usize) -> Option<Branch> {
+        let mut stack = branch.stack.clone();
+        let replacement = self.replacement(stack.pop(), &to.stack_action)?;
+        stack.extend(replacement.iter().rev().flatten());
 
-        // If no transitions matched, return false
-        if matching_transitions.is_empty() {
-            return false;
+        Some(Branch {
+            state: to.state,
+            position: branch.position + consumed,
+            stack,
+        })
+    }
+
+    /// Every branch `branch` leads to in one move
+    fn successors(&self, branch: &Branch) -> Vec<Branch> {
+        let mut symbols = vec![(SymbolId::EPSILON, 0)];
+        if let Some(symbol) = self
+            .input
+            .get(branch.position)
+            .and_then(|&c| self.find_symbol(c))
+        {
+            symbols.push((symbol, 1));
         }
 
-        // Use the first matching transition
-        // In a full non-deterministic PDA implementation, we would explore all possible paths
-        let transition = &matching_transitions[0];
+        let top = branch.stack.last().copied();
+        symbols
+            .into_iter()
+            .flat_map(|(symbol, consumed)| {
+                self.moves(branch.state, top, symbol)
+                    .into_iter()
+                    .filter_map(move |to| self.apply(branch, to, consumed))
+            })
+            .collect()
+    }
+
+    fn accepts(&self, branch: &Branch) -> bool {
+        let acceptance = self.info.acceptance;
+        branch.position == self.input.len()
+            && (!acceptance.needs_final_state() || self.info.final_states.contains(&branch.state))
+            && (!acceptance.needs_empty_stack() || branch.stack.is_empty())
+    }
+
+    /// Why `branch` doesn't accept, when it has read the whole input
+    fn not_accepting(&self, branch: &Branch) -> Rejection {
+        let table = &self.info.table;
+        let state = table.state_name(branch.state).to_string();
 
-        // Update stack based on the stack action
-        match transition.stack_action {
-            StackAction::Push(symbol) => {
-                self.stack.push_back(symbol);
+        if self.info.acceptance.needs_final_state()
+            && !self.info.final_states.contains(&branch.state)
+        {
+            Rejection::NotAccepting { state }
+        } else {
+            Rejection::StackNotEmpty {
+                state,
+                stack: branch
+                    .stack
+                    .iter()
+                    .rev()
+                    .map(|&symbol| table.symbol_name(symbol).to_string())
+                    .collect(),
             }
-            StackAction::Pop => {
-                if !self.stack.is_empty() {
-                    self.stack.pop_back();
-                }
+        }
+    }
+
+    /// Why `branch` rejects, when no branch reads further into the input than it does
+    fn rejection(&self, branch: &Branch) -> Rejection {
+        let table = &self.info.table;
+        let Some(&c) = self.input.get(branch.position) else {
+            return self.not_accepting(branch);
+        };
+        let Some(symbol) = self.find_symbol(c) else {
+            return Rejection::UnknownSymbol { symbol: c };
+        };
+
+        let state = table.state_name(branch.state).to_string();
+        let top = branch.stack.last().copied();
+        // Any move reading on would have been taken, unless it popped the empty stack
+        if !self.moves(branch.state, top, symbol).is_empty() {
+            return Rejection::EmptyStackPopped { state };
+        }
+
+        Rejection::NoTransition {
+            state,
+            symbol: table.symbol_name(symbol).to_string(),
+            stack_top: top.map(|top| table.symbol_name(top).to_string()),
+        }
+    }
+
+    /// The run to follow on the loaded input and how it ends
+    fn search(&self) -> (Vec<Branch>, Verdict) {
+        let start = Self::initial_branch(&self.info);
+
+        let mut exits = Exits::new(self, Goal::Accept);
+        if exits.leads_to_goal(&start) {
+            return (exits.path(start), Verdict::Accepted);
+        }
+
+        // Every head a branch can get to was explored on the way to finding that out
+        let mut exits = Exits::new(self, Goal::Reach(exits.furthest()));
+        let path = exits.path(start);
+        let last = path
+            .last()
+            .expect("the path starts with the initial branch");
+        let rejection = self.rejection(last);
+        (path, Verdict::Rejected(rejection))
+    }
+}
+
+impl super::Machine for Machine {
+    fn build(machine: PartialMachineInfo, src: &str) -> miette::Result<Self> {
+        Ok(Self::new(Info::new(machine, src)?))
+    }
+
+    fn clone_box(&self) -> Box<dyn super::Machine> {
+        Box::new(self.clone())
+    }
+
+    fn reset(&mut self, input: &str) {
+        self.input = input.chars().collect();
+        (self.path, self.verdict) = self.search();
+        self.current = 0;
+    }
+
+    fn step(&mut self) -> Step {
+        if self.current + 1 < self.path.len() {
+            self.current += 1;
+            return Step::Continue;
+        }
+
+        Step::Halt(self.verdict.clone())
+    }
+
+    fn configuration(&self) -> Configuration {
+        let table = &self.info.table;
+        let current = &self.path[self.current];
+
+        Configuration::Pda {
+            state: table.state_name(current.state).to_string(),
+            remaining_input: self.input[current.position..].iter().collect(),
+            stack: current
+                .stack
+                .iter()
+                .rev()
+                .map(|&symbol| table.symbol_name(symbol).to_string())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{Machine as _, grammar::Grammar, parse, strings_up_to};
+    use crate::parser::Parser;
+
+    /// Even length palindromes, guessing where the middle is
+    const PALINDROMES: &str = "
+[states]
+q0, push, pop, acc
+[alphabet]
+a, b
+[stack_alphabet]
+Z, A, B
+[initial]
+q0
+[final]
+acc
+[transitions]
+q0(ε) => (push, PUSH:Z)
+push(a) => (push, PUSH:A)
+push(b) => (push, PUSH:B)
+push(ε) => (pop, NOOP)
+pop(a, A) => (pop, POP)
+pop(b, B) => (pop, POP)
+pop(ε, Z) => (acc, POP)
+";
+
+    /// Any number of `a`, pushing an `A` for each before moving to its final state
+    const PUSH_EACH: &str = "
+[states]
+q, f
+[alphabet]
+a
+[stack_alphabet]
+A
+[initial]
+q
+[final]
+f
+[transitions]
+q(a) => (q, PUSH:A)
+q(ε) => (f, NOOP)
+";
+
+    fn accepts(info: &Info, input: &str) -> bool {
+        Machine::new(info.clone()).run(input).accepted()
+    }
+
+    #[test]
+    fn follows_long_chains_of_epsilon_moves() {
+        let src = "
+[nonterminals]
+S, A
+[terminals]
+a
+[start]
+S
+[productions]
+S => A A A A A A A A a
+A => ε
+";
+        let grammar = Grammar::parse(src, Parser::lex(src).unwrap()).unwrap();
+        let info = grammar.to_pda();
+
+        assert!(accepts(&info, "a"));
+        assert!(!accepts(&info, ""));
+        assert!(!accepts(&info, "aa"));
+    }
+
+    #[test]
+    fn drains_deep_stacks() {
+        let info = Info::new(parse(PUSH_EACH), PUSH_EACH)
+            .unwrap()
+            .converted(Acceptance::EmptyStack);
+
+        for length in [0, 4, 20, 60] {
+            assert!(accepts(&info, &"a".repeat(length)), "a^{length}");
+        }
+    }
+
+    #[test]
+    fn pushes_only_lists_as_several_symbols() {
+        let src = "
+[states]
+q
+[alphabet]
+a
+[stack_alphabet]
+A, B
+[initial]
+q
+[final]
+q
+[transitions]
+q(a) => (q, PUSH:AB)
+";
+        assert!(Info::new(parse(src), src).is_err());
+
+        let src = src.replace("PUSH:AB", "PUSH:[A, B]");
+        let info = Info::new(parse(&src), &src).unwrap();
+        let mut machine = Machine::new(info);
+        machine.reset("a");
+        while machine.step() == Step::Continue {}
+        assert_eq!(
+            machine.configuration(),
+            Configuration::Pda {
+                state: "q".to_string(),
+                remaining_input: String::new(),
+                stack: vec!["A".to_string(), "B".to_string()],
             }
-            StackAction::NoOp => {
-                // Do nothing
+        );
+    }
+
+    #[test]
+    fn conversions_keep_the_language() {
+        for src in [PALINDROMES, PUSH_EACH] {
+            let original = Info::new(parse(src), src).unwrap();
+            let emptying = original.converted(Acceptance::EmptyStack);
+            let back = emptying.converted(Acceptance::FinalState);
+            let both = original.converted(Acceptance::Both);
+
+            let mut original = Machine::new(original);
+            let mut converted = [emptying, back, both].map(Machine::new);
+            for input in strings_up_to("ab", 7) {
+                let expected = original.run(&input).accepted();
+                for machine in &mut converted {
+                    assert_eq!(machine.run(&input).accepted(), expected, "{input:?}");
+                }
             }
         }
+    }
+
+    #[test]
+    fn rejects_despite_epsilon_moves_growing_the_stack() {
+        let src = "
+[states]
+q0, q1
+[alphabet]
+a, b
+[stack_alphabet]
+A
+[initial]
+q0
+[final]
+q1
+[transitions]
+q0(ε) => (q0, PUSH:A)
+q0(a, A) => (q1, POP)
+q1(a, A) => (q1, POP)
+";
+        let info = Info::new(parse(src), src).unwrap();
+
+        assert!(accepts(&info, "aaa"));
+        let outcome = Machine::new(info).run("aab");
+        assert_eq!(
+            outcome.verdict,
+            Verdict::Rejected(Rejection::NoTransition {
+                state: "q1".to_string(),
+                symbol: "b".to_string(),
+                stack_top: None,
+            })
+        );
+    }
+
+    #[test]
+    fn traces_the_accepting_run() {
+        let mut machine = Machine::new(Info::new(parse(PALINDROMES), PALINDROMES).unwrap());
+        let mut trace = vec![];
+        let outcome = machine.run_traced("abba", &mut |configuration| {
+            trace.push(configuration.to_string())
+        });
+
+        assert!(outcome.accepted());
+        assert_eq!(outcome.steps, 7);
+        assert_eq!(
+            trace,
+            [
+                "(q0, \"abba\", [])",
+                "(push, \"abba\", [Z])",
+                "(push, \"bba\", [A, Z])",
+                "(push, \"ba\", [B, A, Z])",
+                "(pop, \"ba\", [B, A, Z])",
+                "(pop, \"a\", [A, Z])",
+                "(pop, \"\", [Z])",
+                "(acc, \"\", [])",
+            ]
+        );
+    }
+
+    #[test]
+    fn traces_a_run_reading_the_most_of_a_rejected_input() {
+        let mut machine = Machine::new(Info::new(parse(PALINDROMES), PALINDROMES).unwrap());
+        let mut trace = vec![];
+        let outcome = machine.run_traced("abc", &mut |configuration| {
+            trace.push(configuration.to_string())
+        });
+
+        assert_eq!(
+            outcome.verdict,
+            Verdict::Rejected(Rejection::UnknownSymbol { symbol: 'c' })
+        );
+        assert_eq!(outcome.steps, trace.len() - 1);
+        assert_eq!(trace.last().unwrap(), "(push, \"c\", [B, A, Z])");
+    }
+
+    /// Pops without looking at the stack, which is empty
+    const POP_EMPTY: &str = "
+[states]
+q0, q1
+[alphabet]
+a
+[stack_alphabet]
+A
+[initial]
+q0
+[final]
+q1
+[transitions]
+q0(a) => (q1, POP)
+";
+
+    #[test]
+    fn popping_the_empty_stack_only_survives_when_lenient() {
+        let lenient = Info::new(parse(POP_EMPTY), POP_EMPTY).unwrap();
+        assert!(accepts(&lenient, "a"));
+
+        let src = format!("{POP_EMPTY}[stack_semantics]\nstrict\n");
+        let strict = Info::new(parse(&src), &src).unwrap();
+        assert!(!accepts(&strict, "a"));
+    }
+
+    /// Checks the PDA agrees with the one written out for it and read back, after
+    /// being converted to accept by empty stack
+    fn converts_through_its_file(info: &Info) {
+        let written = info.converted(Acceptance::EmptyStack).to_string();
+        let back = Info::new(parse(&written), &written).unwrap();
+
+        let mut original = Machine::new(info.clone());
+        let mut back = Machine::new(back);
+        for input in strings_up_to("abc", 4) {
+            let expected = original.run(&input).accepted();
+            assert_eq!(back.run(&input).accepted(), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn star_matches_any_symbol_on_top() {
+        let src = "
+[states]
+q0, q1, q2
+[alphabet]
+a, b, c
+[stack_alphabet]
+A, B
+[initial]
+q0
+[final]
+q2
+[transitions]
+q0(a) => (q1, PUSH:A)
+q0(b) => (q1, PUSH:B)
+q0(c) => (q1, NOOP)
+q1(ε, *) => (q2, POP)
+";
+        let info = Info::new(parse(src), src).unwrap();
+
+        assert!(accepts(&info, "a"));
+        assert!(accepts(&info, "b"));
+        assert!(!accepts(&info, "c"));
+        converts_through_its_file(&info);
+    }
+
+    #[test]
+    fn declared_star_is_a_symbol_of_its_own() {
+        let src = "
+[states]
+q0, q1, q2
+[alphabet]
+a, b
+[stack_alphabet]
+*, A
+[initial]
+q0
+[final]
+q2
+[transitions]
+q0(a) => (q1, PUSH:*)
+q0(b) => (q1, PUSH:A)
+q1(ε, *) => (q2, POP)
+";
+        let info = Info::new(parse(src), src).unwrap();
 
-        // Update current state
-        self.current_state = transition.state;
-        true
+        assert!(accepts(&info, "a"));
+        assert!(!accepts(&info, "b"));
+        converts_through_its_file(&info);
+        let written = info.converted(Acceptance::EmptyStack).to_string();
+        assert!(written.contains("drain(ε, ⊥) => (drain, POP)"));
     }
 }
@@ -0,0 +1,199 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::{
+    TransitionFrom, TransitionTo, dfa,
+    symbols::{StateId, SymbolId, SymbolTable, shared_alphabet},
+};
+
+/// How the product of two DFAs decides acceptance out of its two halves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Union,
+    Intersection,
+    /// Accepted by the first DFA but not by the second
+    Difference,
+    /// Accepted by exactly one of the two DFAs
+    SymmetricDifference,
+}
+
+impl Operation {
+    fn accepts(self, first: bool, second: bool) -> bool {
+        match self {
+            Operation::Union => first || second,
+            Operation::Intersection => first && second,
+            Operation::Difference => first && !second,
+            Operation::SymmetricDifference => first != second,
+        }
+    }
+}
+
+/// Move of a DFA on a symbol given by name. `None` is the dead state standing in for
+/// the symbols a DFA doesn't know about
+fn next(info: &dfa::Info, state: Option<StateId>, symbol: &str) -> Option<StateId> {
+    let symbol = info
+        .table
+        .symbol(symbol)
+        .filter(|symbol| info.alphabet.contains(symbol))?;
+    Some(info.next(state?, symbol))
+}
+
+/// Runs both DFAs side by side over the union of their alphabets. Each state of the
+/// product is a pair of states named `first_second`, and only the pairs reachable from
+/// the initial pair are kept. A DFA reading a symbol outside its alphabet moves to a
+/// dead state, written `∅` in the names
+pub fn product(first: &dfa::Info, second: &dfa::Info, operation: Operation) -> dfa::Info {
+    let alphabet = shared_alphabet([
+        (&first.table, &first.alphabet),
+        (&second.table, &second.alphabet),
+    ]);
+
+    let mut table = SymbolTable::new();
+    let new_alphabet: Vec<SymbolId> = alphabet
+        .iter()
+        .map(|symbol| table.intern_symbol(symbol))
+        .collect();
+
+    let name = |info: &dfa::Info, state: Option<StateId>| match state {
+        Some(state) => info.table.state_name(state).to_string(),
+        None => "∅".to_string(),
+    };
+
+    let start = (Some(first.start_state), Some(second.start_state));
+    let start_state = table.fresh_state(&format!(
+        "{}_{}",
+        name(first, start.0),
+        name(second, start.1)
+    ));
+
+    let mut ids = HashMap::from([(start, start_state)]);
+    let mut queue = VecDeque::from([start]);
+    let mut transitions = HashMap::new();
+    let mut final_states = HashSet::new();
+
+    while let Some(pair) = queue.pop_front() {
+        let id = ids[&pair];
+        let accepts = |info: &dfa::Info, state: Option<StateId>| {
+            state.is_some_and(|state| info.final_states.contains(&state))
+        };
+        if operation.accepts(accepts(first, pair.0), accepts(second, pair.1)) {
+            final_states.insert(id);
+        }
+
+        for (symbol, &new_symbol) in alphabet.iter().zip(&new_alphabet) {
+            let target = (next(first, pair.0, symbol), next(second, pair.1, symbol));
+            let target_id = match ids.get(&target) {
+                Some(&target_id) => target_id,
+                None => {
+                    let target_id = table.fresh_state(&format!(
+                        "{}_{}",
+                        name(first, target.0),
+                        name(second, target.1)
+                    ));
+                    ids.insert(target, target_id);
+                    queue.push_back(target);
+                    target_id
+                }
+            };
+
+            transitions.insert(
+                TransitionFrom {
+                    initial: id,
+                    with_symbol: new_symbol,
+                },
+                TransitionTo(target_id),
+            );
+        }
+    }
+
+    dfa::Info {
+        table,
+        alphabet: new_alphabet.into_iter().collect(),
+        transitions,
+        start_state,
+        final_states,
+    }
+}
+
+/// DFA accepting every string over the same alphabet the given one rejects. A DFA is
+/// always complete, so flipping its final states is enough
+pub fn complement(info: &dfa::Info) -> dfa::Info {
+    let final_states = info
+        .table
+        .states()
+        .filter(|state| !info.final_states.contains(state))
+        .collect();
+
+    dfa::Info {
+        final_states,
+        ..info.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{Machine as _, parse, regex::Regex, strings_up_to, subset};
+
+    fn dfa(pattern: &str) -> dfa::Info {
+        let alphabet = ["a", "b"].map(String::from);
+        subset::determinize(&Regex::parse(pattern).unwrap().to_nfa(Some(&alphabet)))
+    }
+
+    /// Checks the DFA accepts exactly the strings over `ab` satisfying `expected`, and
+    /// that it reads back from the file it is written as
+    fn check(info: dfa::Info, expected: impl Fn(&str) -> bool) {
+        let written = info.to_string();
+        dfa::Info::new(parse(&written), &written).unwrap();
+
+        let mut machine = dfa::Machine::new(info);
+        for input in strings_up_to("ab", 6) {
+            assert_eq!(
+                machine.run(&input).accepted(),
+                expected(&input),
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn operations_combine_the_languages() {
+        let (first, second) = (dfa("(a|b)*aa(a|b)*"), dfa("(a|b)*b"));
+        let both = |input: &str| (input.contains("aa"), input.ends_with('b'));
+
+        check(product(&first, &second, Operation::Union), |input| {
+            let (first, second) = both(input);
+            first || second
+        });
+        check(product(&first, &second, Operation::Intersection), |input| {
+            let (first, second) = both(input);
+            first && second
+        });
+        check(product(&first, &second, Operation::Difference), |input| {
+            let (first, second) = both(input);
+            first && !second
+        });
+        check(
+            product(&first, &second, Operation::SymmetricDifference),
+            |input| {
+                let (first, second) = both(input);
+                first != second
+            },
+        );
+    }
+
+    #[test]
+    fn complement_flips_every_string() {
+        check(complement(&dfa("(a|b)*aa(a|b)*")), |input| {
+            !input.contains("aa")
+        });
+    }
+
+    #[test]
+    fn symbols_outside_an_alphabet_lead_to_the_dead_state() {
+        let only_a = subset::determinize(&Regex::parse("a*").unwrap().to_nfa(None));
+        let union = product(&only_a, &dfa("b"), Operation::Union);
+        assert!(union.to_string().contains('∅'));
+
+        check(union, |input| input == "b" || !input.contains('b'));
+    }
+}
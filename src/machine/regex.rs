@@ -0,0 +1,710 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    iter::Peekable,
+    str::CharIndices,
+};
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use super::{
+    TransitionFrom, TransitionTo, nfa,
+    symbols::{SymbolId, SymbolTable},
+};
+
+#[derive(Error, Diagnostic, Debug)]
+pub enum RegexError {
+    #[error("Unexpected character")]
+    #[diagnostic(help("expected to find {}", expected))]
+    UnexpectedCharacter {
+        #[label("here")]
+        at: SourceSpan,
+        expected: &'static str,
+    },
+    #[error("Unclosed group")]
+    UnclosedGroup {
+        #[label("this ( is never closed")]
+        at: SourceSpan,
+    },
+    #[error("Unclosed character class")]
+    UnclosedClass {
+        #[label("this [ is never closed")]
+        at: SourceSpan,
+    },
+    #[error("Unclosed symbol name")]
+    UnclosedName {
+        #[label("this {{ is never closed")]
+        at: SourceSpan,
+    },
+    #[error("Nothing to repeat")]
+    NothingToRepeat {
+        #[label("this applies to nothing")]
+        at: SourceSpan,
+    },
+    #[error("Invalid range in character class")]
+    InvalidRange {
+        #[label("range goes backwards")]
+        at: SourceSpan,
+    },
+    #[error("Symbol cannot be written in a machine file")]
+    #[diagnostic(help("machine files use ( ) [ ] , : = # and whitespace as separators"))]
+    UnwritableSymbol {
+        #[label("this symbol")]
+        at: SourceSpan,
+    },
+}
+
+/// Regular expression over the symbols of an alphabet
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Regex {
+    /// Matches nothing at all, `∅`
+    Empty,
+    /// Matches the empty string, `ε`
+    Epsilon,
+    Symbol(String),
+    /// Any one symbol of the list, or any symbol of the alphabet but those when negated.
+    /// `.` is the negation of an empty list
+    Class {
+        negated: bool,
+        symbols: Vec<String>,
+    },
+    Union(Vec<Regex>),
+    Concat(Vec<Regex>),
+    Star(Box<Regex>),
+    Plus(Box<Regex>),
+    Optional(Box<Regex>),
+}
+
+/// Characters with a meaning of their own in patterns
+const OPERATORS: &str = "|*+?()[]{}.\\";
+/// Characters the lexer of machine files splits on, so they can't be symbols
+const SEPARATORS: &str = "()[],:=#{}";
+
+struct RegexParser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    len: usize,
+}
+
+impl RegexParser<'_> {
+    /// Next character that isn't whitespace, without consuming it
+    fn peek(&mut self) -> Option<(usize, char)> {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+        self.chars.peek().copied()
+    }
+
+    fn symbol(at: usize, c: char) -> Result<String, RegexError> {
+        if SEPARATORS.contains(c) || c.is_whitespace() {
+            return Err(RegexError::UnwritableSymbol {
+                at: SourceSpan::new(at.into(), c.len_utf8()),
+            });
+        }
+        Ok(c.to_string())
+    }
+
+    fn union(&mut self) -> Result<Regex, RegexError> {
+        let mut alternatives = vec![self.concat()?];
+        while let Some((_, '|')) = self.peek() {
+            self.chars.next();
+            alternatives.push(self.concat()?);
+        }
+
+        Ok(if alternatives.len() == 1 {
+            alternatives.pop().unwrap()
+        } else {
+            Regex::Union(alternatives)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Regex, RegexError> {
+        let mut parts = vec![];
+        while let Some((_, c)) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            parts.push(self.repeat()?);
+        }
+
+        Ok(match parts.len() {
+            0 => Regex::Epsilon,
+            1 => parts.pop().unwrap(),
+            _ => Regex::Concat(parts),
+        })
+    }
+
+    fn repeat(&mut self) -> Result<Regex, RegexError> {
+        let mut regex = self.atom()?;
+        while let Some((_, c)) = self.peek() {
+            regex = match c {
+                '*' => Regex::Star(Box::new(regex)),
+                '+' => Regex::Plus(Box::new(regex)),
+                '?' => Regex::Optional(Box::new(regex)),
+                _ => break,
+            };
+            self.chars.next();
+        }
+        Ok(regex)
+    }
+
+    fn atom(&mut self) -> Result<Regex, RegexError> {
+        let Some((at, c)) = self.chars.next() else {
+            return Err(RegexError::UnexpectedCharacter {
+                at: SourceSpan::new(self.len.into(), 0),
+                expected: "a symbol",
+            });
+        };
+
+        match c {
+            '(' => {
+                let inner = self.union()?;
+                match self.peek() {
+                    Some((_, ')')) => {
+                        self.chars.next();
+                        Ok(inner)
+                    }
+                    _ => Err(RegexError::UnclosedGroup {
+                        at: SourceSpan::new(at.into(), 1),
+                    }),
+                }
+            }
+            '[' => self.class(at),
+            '{' => self.name(at),
+            '.' => Ok(Regex::Class {
+                negated: true,
+                symbols: vec![],
+            }),
+            'ε' => Ok(Regex::Epsilon),
+            '∅' => Ok(Regex::Empty),
+            '\\' => match self.chars.next() {
+                Some((at, c)) => Ok(Regex::Symbol(Self::symbol(at, c)?)),
+                None => Err(RegexError::UnexpectedCharacter {
+                    at: SourceSpan::new(at.into(), 1),
+                    expected: "a character to escape",
+                }),
+            },
+            '*' | '+' | '?' => Err(RegexError::NothingToRepeat {
+                at: SourceSpan::new(at.into(), 1),
+            }),
+            ')' | '|' => Err(RegexError::UnexpectedCharacter {
+                at: SourceSpan::new(at.into(), 1),
+                expected: "a symbol",
+            }),
+            c => Ok(Regex::Symbol(Self::symbol(at, c)?)),
+        }
+    }
+
+    /// `{zero}`, a symbol with a name of several characters, the opening brace already
+    /// consumed
+    fn name(&mut self, open: usize) -> Result<Regex, RegexError> {
+        let mut name = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '}')) => break,
+                Some((at, c)) => name.push_str(&Self::symbol(at, c)?),
+                None => {
+                    return Err(RegexError::UnclosedName {
+                        at: SourceSpan::new(open.into(), 1),
+                    });
+                }
+            }
+        }
+
+        if name.is_empty() {
+            return Err(RegexError::UnexpectedCharacter {
+                at: SourceSpan::new((open + 1).into(), 1),
+                expected: "a symbol name",
+            });
+        }
+        Ok(Regex::Symbol(name))
+    }
+
+    /// `[abc]`, `[a-z]` or `[^ab]`, the opening bracket already consumed
+    fn class(&mut self, open: usize) -> Result<Regex, RegexError> {
+        let unclosed = || RegexError::UnclosedClass {
+            at: SourceSpan::new(open.into(), 1),
+        };
+
+        let negated = matches!(self.chars.peek(), Some((_, '^')));
+        if negated {
+            self.chars.next();
+        }
+
+        let mut symbols = vec![];
+        loop {
+            let (at, c) = match self.chars.next() {
+                Some((_, ']')) => break,
+                Some((_, '\\')) => self.chars.next().ok_or_else(unclosed)?,
+                Some(next) => next,
+                None => return Err(unclosed()),
+            };
+
+            if let Some((_, '-')) = self.chars.peek() {
+                self.chars.next();
+                let Some((end_at, end)) = self.chars.next() else {
+                    return Err(unclosed());
+                };
+                if end < c {
+                    return Err(RegexError::InvalidRange {
+                        at: SourceSpan::new(at.into(), end_at + end.len_utf8() - at),
+                    });
+                }
+                for c in c..=end {
+                    symbols.push(Self::symbol(at, c)?);
+                }
+            } else {
+                symbols.push(Self::symbol(at, c)?);
+            }
+        }
+
+        Ok(Regex::Class { negated, symbols })
+    }
+}
+
+impl Regex {
+    /// Parses a pattern made of symbols, `|`, `*`, `+`, `?`, groups, character classes,
+    /// `.` for any symbol, `ε` and `∅`. Whitespace is ignored, `\` escapes operators and
+    /// symbols with longer names are written in braces, like `{zero}`
+    pub fn parse(pattern: &str) -> Result<Regex, RegexError> {
+        let mut parser = RegexParser {
+            chars: pattern.char_indices().peekable(),
+            len: pattern.len(),
+        };
+
+        let regex = parser.union()?;
+        match parser.peek() {
+            None => Ok(regex),
+            Some((at, _)) => Err(RegexError::UnexpectedCharacter {
+                at: SourceSpan::new(at.into(), 1),
+                expected: "| or the end of the pattern",
+            }),
+        }
+    }
+
+    /// Whether the expression obviously matches the empty string
+    fn is_nullable(&self) -> bool {
+        matches!(self, Regex::Epsilon | Regex::Star(_) | Regex::Optional(_))
+    }
+
+    /// `a|b`, with `∅` dropped, duplicates removed and `ε|a` written `a?`
+    pub fn union(a: Regex, b: Regex) -> Regex {
+        fn alternatives(regex: Regex) -> Vec<Regex> {
+            match regex {
+                Regex::Union(alternatives) => alternatives,
+                Regex::Optional(inner) => {
+                    let mut inner = alternatives(*inner);
+                    inner.insert(0, Regex::Epsilon);
+                    inner
+                }
+                regex => vec![regex],
+            }
+        }
+
+        let mut parts = vec![];
+        for regex in [a, b] {
+            for alternative in alternatives(regex) {
+                if alternative != Regex::Empty && !parts.contains(&alternative) {
+                    parts.push(alternative);
+                }
+            }
+        }
+
+        let nullable = parts.contains(&Regex::Epsilon);
+        parts.retain(|part| *part != Regex::Epsilon);
+
+        let regex = match parts.len() {
+            0 if nullable => return Regex::Epsilon,
+            0 => return Regex::Empty,
+            1 => parts.pop().unwrap(),
+            _ => Regex::Union(parts),
+        };
+
+        match regex {
+            _ if !nullable || regex.is_nullable() => regex,
+            Regex::Plus(inner) => Regex::Star(inner),
+            regex => Regex::Optional(Box::new(regex)),
+        }
+    }
+
+    /// `ab`, with `ε` dropped, `∅` swallowing everything and `aa*` written `a+`
+    pub fn concat(a: Regex, b: Regex) -> Regex {
+        let mut parts: Vec<Regex> = vec![];
+        for regex in [a, b] {
+            let regex_parts = match regex {
+                Regex::Empty => return Regex::Empty,
+                Regex::Epsilon => vec![],
+                Regex::Concat(parts) => parts,
+                regex => vec![regex],
+            };
+
+            for part in regex_parts {
+                match (parts.last(), &part) {
+                    (Some(Regex::Star(last)), Regex::Star(inner)) if last == inner => {}
+                    (Some(last), Regex::Star(inner)) if **inner == *last => {
+                        *parts.last_mut().unwrap() = Regex::Plus(inner.clone());
+                    }
+                    (Some(Regex::Star(last)), part) if **last == *part => {
+                        *parts.last_mut().unwrap() = Regex::Plus(last.clone());
+                    }
+                    _ => parts.push(part),
+                }
+            }
+        }
+
+        match parts.len() {
+            0 => Regex::Epsilon,
+            1 => parts.pop().unwrap(),
+            _ => Regex::Concat(parts),
+        }
+    }
+
+    /// `a*`, with `∅*` and `ε*` written `ε` and nested repetitions collapsed
+    pub fn star(regex: Regex) -> Regex {
+        match regex {
+            Regex::Empty | Regex::Epsilon => Regex::Epsilon,
+            Regex::Star(inner) | Regex::Plus(inner) | Regex::Optional(inner) => Regex::star(*inner),
+            regex => Regex::Star(Box::new(regex)),
+        }
+    }
+
+    /// Every symbol written out in the expression, in order of appearance
+    pub fn symbols(&self) -> Vec<String> {
+        fn collect(regex: &Regex, out: &mut Vec<String>) {
+            match regex {
+                Regex::Empty | Regex::Epsilon => {}
+                Regex::Symbol(symbol) => {
+                    if !out.contains(symbol) {
+                        out.push(symbol.clone());
+                    }
+                }
+                Regex::Class { symbols, .. } => {
+                    for symbol in symbols {
+                        if !out.contains(symbol) {
+                            out.push(symbol.clone());
+                        }
+                    }
+                }
+                Regex::Union(parts) | Regex::Concat(parts) => {
+                    for part in parts {
+                        collect(part, out);
+                    }
+                }
+                Regex::Star(inner) | Regex::Plus(inner) | Regex::Optional(inner) => {
+                    collect(inner, out);
+                }
+            }
+        }
+
+        let mut out = vec![];
+        collect(self, &mut out);
+        out
+    }
+
+    /// Builds an ε-NFA with Thompson's construction. The alphabet is `alphabet` when
+    /// given, and the symbols of the expression otherwise, and is what `.` and negated
+    /// classes range over
+    pub fn to_nfa(&self, alphabet: Option<&[String]>) -> nfa::Info {
+        let mut symbols = match alphabet {
+            Some(alphabet) => alphabet.to_vec(),
+            None => vec![],
+        };
+        for symbol in self.symbols() {
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+
+        let mut builder = Thompson {
+            alphabet: &symbols,
+            states: 0,
+            moves: vec![],
+        };
+        let (start, end) = builder.fragment(self);
+
+        // Name states q0, q1, ... in the order they are reached from the start
+        let mut order = HashMap::from([(start, 0)]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(state) = queue.pop_front() {
+            for (_, _, to) in builder.moves.iter().filter(|(from, _, _)| *from == state) {
+                if !order.contains_key(to) {
+                    order.insert(*to, order.len());
+                    queue.push_back(*to);
+                }
+            }
+        }
+        // The end state may be unreachable, for `∅`
+        if !order.contains_key(&end) {
+            order.insert(end, order.len());
+        }
+
+        let mut table = SymbolTable::new();
+        let states: Vec<_> = (0..order.len())
+            .map(|index| table.intern_state(&format!("q{index}")))
+            .collect();
+        let mut alphabet = HashSet::from([SymbolId::EPSILON]);
+        for symbol in &symbols {
+            alphabet.insert(table.intern_symbol(symbol));
+        }
+
+        let mut transitions: HashMap<TransitionFrom, Vec<TransitionTo>> = HashMap::new();
+        for (from, symbol, to) in &builder.moves {
+            // Moves out of the states stranded behind a `∅` can never be taken
+            let Some(&from) = order.get(from) else {
+                continue;
+            };
+            let with_symbol = match symbol {
+                Some(symbol) => table.symbol(symbol).expect("alphabet covers every symbol"),
+                None => SymbolId::EPSILON,
+            };
+
+            transitions
+                .entry(TransitionFrom {
+                    initial: states[from],
+                    with_symbol,
+                })
+                .or_default()
+                .push(TransitionTo(states[order[to]]));
+        }
+
+        nfa::Info {
+            table,
+            alphabet,
+            transitions,
+            start_state: states[order[&start]],
+            final_states: HashSet::from([states[order[&end]]]),
+        }
+    }
+}
+
+/// Thompson's construction: every sub-expression becomes a fragment with a single entry
+/// and a single exit state, glued to the others with epsilon moves
+struct Thompson<'a> {
+    alphabet: &'a [String],
+    states: usize,
+    /// `(from, symbol, to)`, `None` standing for an epsilon move
+    moves: Vec<(usize, Option<String>, usize)>,
+}
+
+impl Thompson<'_> {
+    fn state(&mut self) -> usize {
+        self.states += 1;
+        self.states - 1
+    }
+
+    fn fragment(&mut self, regex: &Regex) -> (usize, usize) {
+        let (start, end) = (self.state(), self.state());
+
+        match regex {
+            Regex::Empty => {}
+            Regex::Epsilon => self.moves.push((start, None, end)),
+            Regex::Symbol(symbol) => self.moves.push((start, Some(symbol.clone()), end)),
+            Regex::Class { negated, symbols } => {
+                for symbol in self.alphabet {
+                    if symbols.contains(symbol) != *negated {
+                        self.moves.push((start, Some(symbol.clone()), end));
+                    }
+                }
+            }
+            Regex::Union(alternatives) => {
+                for alternative in alternatives {
+                    let (inner_start, inner_end) = self.fragment(alternative);
+                    self.moves.push((start, None, inner_start));
+                    self.moves.push((inner_end, None, end));
+                }
+            }
+            Regex::Concat(parts) => {
+                let mut last = start;
+                for part in parts {
+                    let (inner_start, inner_end) = self.fragment(part);
+                    self.moves.push((last, None, inner_start));
+                    last = inner_end;
+                }
+                self.moves.push((last, None, end));
+            }
+            Regex::Star(inner) | Regex::Plus(inner) | Regex::Optional(inner) => {
+                let (inner_start, inner_end) = self.fragment(inner);
+                self.moves.push((start, None, inner_start));
+                self.moves.push((inner_end, None, end));
+
+                if !matches!(regex, Regex::Plus(_)) {
+                    self.moves.push((start, None, end));
+                }
+                if !matches!(regex, Regex::Optional(_)) {
+                    self.moves.push((inner_end, None, inner_start));
+                }
+            }
+        }
+
+        (start, end)
+    }
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Binding strength, to only print the parentheses that are needed
+        fn precedence(regex: &Regex) -> u8 {
+            match regex {
+                Regex::Union(_) => 0,
+                Regex::Concat(_) => 1,
+                Regex::Star(_) | Regex::Plus(_) | Regex::Optional(_) => 2,
+                _ => 3,
+            }
+        }
+
+        fn write(f: &mut fmt::Formatter<'_>, regex: &Regex, min: u8) -> fmt::Result {
+            if precedence(regex) < min {
+                write!(f, "(")?;
+                write(f, regex, 0)?;
+                return write!(f, ")");
+            }
+
+            match regex {
+                Regex::Empty => write!(f, "∅"),
+                Regex::Epsilon => write!(f, "ε"),
+                Regex::Symbol(symbol) => {
+                    if symbol.chars().count() > 1 {
+                        // Run together, `zero` would read as four symbols
+                        return write!(f, "{{{symbol}}}");
+                    }
+                    if OPERATORS.contains(symbol.as_str()) {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{symbol}")
+                }
+                Regex::Class { negated, symbols } => {
+                    if *negated && symbols.is_empty() {
+                        return write!(f, ".");
+                    }
+                    write!(f, "[{}", if *negated { "^" } else { "" })?;
+                    for symbol in symbols {
+                        if matches!(symbol.as_str(), "]" | "\\" | "^" | "-") {
+                            write!(f, "\\")?;
+                        }
+                        write!(f, "{symbol}")?;
+                    }
+                    write!(f, "]")
+                }
+                Regex::Union(alternatives) => {
+                    for (index, alternative) in alternatives.iter().enumerate() {
+                        if index > 0 {
+                            write!(f, "|")?;
+                        }
+                        write(f, alternative, 1)?;
+                    }
+                    Ok(())
+                }
+                Regex::Concat(parts) => {
+                    for part in parts {
+                        write(f, part, 2)?;
+                    }
+                    Ok(())
+                }
+                Regex::Star(inner) => {
+                    write(f, inner, 3)?;
+                    write!(f, "*")
+                }
+                Regex::Plus(inner) => {
+                    write(f, inner, 3)?;
+                    write!(f, "+")
+                }
+                Regex::Optional(inner) => {
+                    write(f, inner, 3)?;
+                    write!(f, "?")
+                }
+            }
+        }
+
+        write(f, self, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{
+        Machine as _, elimination,
+        equivalence::{self, Equivalence},
+        strings_up_to,
+    };
+
+    fn thompson(pattern: &str, alphabet: Option<&[String]>) -> nfa::Machine {
+        nfa::Machine::new(Regex::parse(pattern).unwrap().to_nfa(alphabet))
+    }
+
+    #[test]
+    fn thompson_nfas_accept_the_pattern() {
+        let mut machine = thompson("(a|b)*abb", None);
+        for input in strings_up_to("ab", 7) {
+            let expected = input.ends_with("abb");
+            assert_eq!(machine.run(&input).accepted(), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn classes_range_over_the_alphabet() {
+        let alphabet = ["a", "b", "c"].map(String::from);
+        let mut machine = thompson("[^a]+", Some(&alphabet));
+        for input in strings_up_to("abc", 4) {
+            let expected = !input.is_empty() && !input.contains('a');
+            assert_eq!(machine.run(&input).accepted(), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn patterns_matching_nothing_build() {
+        for (pattern, language) in [
+            ("∅", vec![]),
+            ("∅*", vec![""]),
+            ("∅a", vec![]),
+            ("a|∅", vec!["a"]),
+            ("[^a]", vec![]),
+        ] {
+            let mut machine = thompson(pattern, None);
+            for input in ["", "a", "aa"] {
+                let expected = language.contains(&input);
+                assert_eq!(
+                    machine.run(input).accepted(),
+                    expected,
+                    "{pattern} on {input:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn longer_symbol_names_read_back() {
+        let regex = Regex::Concat(vec![
+            Regex::Symbol("zero".to_string()),
+            Regex::Star(Box::new(Regex::Symbol("one".to_string()))),
+            Regex::Symbol("*".to_string()),
+        ]);
+
+        assert_eq!(regex.to_string(), "{zero}{one}*\\*");
+        assert_eq!(Regex::parse(&regex.to_string()).unwrap(), regex);
+    }
+
+    #[test]
+    fn state_elimination_keeps_the_language() {
+        for pattern in [
+            "(a|b)*abb",
+            "a?b+|ε",
+            "(ab|ba)*(a|∅)",
+            "[^a]+a.",
+            "{zero}({one}|{zero}{zero})*",
+        ] {
+            let nfa = Regex::parse(pattern).unwrap().to_nfa(None);
+            let printed = elimination::to_regex(&nfa).to_string();
+            let back = Regex::parse(&printed).unwrap().to_nfa(None);
+
+            assert_eq!(
+                equivalence::compare(&nfa, &back),
+                Equivalence::Equivalent,
+                "{pattern} came back as {printed}"
+            );
+        }
+    }
+}
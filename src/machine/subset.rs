@@ -0,0 +1,203 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use super::{
+    TransitionFrom, TransitionTo, dfa, nfa,
+    symbols::{StateId, SymbolId, SymbolTable},
+};
+
+/// Set of NFA states, standing for a single state of the equivalent DFA
+pub type Subset = BTreeSet<StateId>;
+
+/// Names a set of NFA states the way the lexer reads it back, `{q0,q2}`
+fn subset_name(table: &SymbolTable, subset: &Subset) -> String {
+    let names: Vec<&str> = subset
+        .iter()
+        .map(|&state| table.state_name(state))
+        .collect();
+    format!("{{{}}}", names.join(","))
+}
+
+/// The DFA the powerset construction builds out of an NFA, walked on demand instead of
+/// being built upfront. Symbols are looked up by name, so the walk can be driven by
+/// the alphabet of another machine
+#[derive(Debug, Clone, Copy)]
+pub struct Powerset<'a> {
+    pub info: &'a nfa::Info,
+}
+
+impl<'a> Powerset<'a> {
+    pub fn new(info: &'a nfa::Info) -> Self {
+        Self { info }
+    }
+
+    /// Symbols of the NFA in declaration order, epsilon left out
+    pub fn alphabet(&self) -> Vec<SymbolId> {
+        let mut alphabet: Vec<SymbolId> = self
+            .info
+            .alphabet
+            .iter()
+            .copied()
+            .filter(|&symbol| symbol != SymbolId::EPSILON)
+            .collect();
+        alphabet.sort_unstable();
+        alphabet
+    }
+
+    pub fn start(&self) -> Subset {
+        self.info
+            .epsilon_closure(HashSet::from([self.info.start_state]))
+            .into_iter()
+            .collect()
+    }
+
+    /// Subset reached from `subset` on `symbol`. Symbols outside the alphabet lead to
+    /// the empty subset, the dead state
+    pub fn next(&self, subset: &Subset, symbol: &str) -> Subset {
+        let Some(symbol) =
+            self.info.table.symbol(symbol).filter(|symbol| {
+                *symbol != SymbolId::EPSILON && self.info.alphabet.contains(symbol)
+            })
+        else {
+            return Subset::new();
+        };
+
+        let mut targets = HashSet::new();
+        for &state in subset {
+            let key = TransitionFrom {
+                initial: state,
+                with_symbol: symbol,
+            };
+
+            if let Some(moves) = self.info.transitions.get(&key) {
+                targets.extend(moves.iter().map(|to| to.0));
+            }
+        }
+
+        self.info.epsilon_closure(targets).into_iter().collect()
+    }
+
+    pub fn accepts(&self, subset: &Subset) -> bool {
+        subset
+            .iter()
+            .any(|state| self.info.final_states.contains(state))
+    }
+}
+
+/// Builds the DFA equivalent to an NFA with the powerset construction. Only subsets
+/// reachable from the initial one become states, in the order they are discovered, and
+/// the empty subset `{}` is kept as a dead state so the DFA is complete
+pub fn determinize(info: &nfa::Info) -> dfa::Info {
+    let powerset = Powerset::new(info);
+    let alphabet: Vec<&str> = powerset
+        .alphabet()
+        .into_iter()
+        .map(|symbol| info.table.symbol_name(symbol))
+        .collect();
+
+    let mut table = SymbolTable::new();
+    let new_alphabet: Vec<SymbolId> = alphabet
+        .iter()
+        .map(|symbol| table.intern_symbol(symbol))
+        .collect();
+
+    let start = powerset.start();
+    let start_state = table.intern_state(&subset_name(&info.table, &start));
+
+    let mut ids: HashMap<Subset, StateId> = HashMap::from([(start.clone(), start_state)]);
+    let mut queue = VecDeque::from([start]);
+    let mut transitions = HashMap::new();
+    let mut final_states = HashSet::new();
+
+    while let Some(subset) = queue.pop_front() {
+        let id = ids[&subset];
+        if powerset.accepts(&subset) {
+            final_states.insert(id);
+        }
+
+        for (symbol, &new_symbol) in alphabet.iter().zip(&new_alphabet) {
+            let target = powerset.next(&subset, symbol);
+            let target_id = match ids.get(&target) {
+                Some(&target_id) => target_id,
+                None => {
+                    let target_id = table.intern_state(&subset_name(&info.table, &target));
+                    ids.insert(target.clone(), target_id);
+                    queue.push_back(target);
+                    target_id
+                }
+            };
+
+            transitions.insert(
+                TransitionFrom {
+                    initial: id,
+                    with_symbol: new_symbol,
+                },
+                TransitionTo(target_id),
+            );
+        }
+    }
+
+    dfa::Info {
+        table,
+        alphabet: new_alphabet.into_iter().collect(),
+        transitions,
+        start_state,
+        final_states,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{
+        equivalence::{self, Equivalence},
+        parse,
+    };
+
+    #[test]
+    fn determinized_files_read_back_as_equivalent_dfas() {
+        for src in [
+            include_str!("../../nfa_accept.txt"),
+            include_str!("../../nfa_complex.txt"),
+        ] {
+            let nfa = nfa::Info::new(parse(src), src).unwrap();
+            let written = determinize(&nfa).to_string();
+
+            dfa::Info::new(parse(&written), &written).unwrap();
+            let back = nfa::Info::new(parse(&written), &written).unwrap();
+            assert_eq!(equivalence::compare(&nfa, &back), Equivalence::Equivalent);
+        }
+    }
+
+    #[test]
+    fn determinizes_its_own_output_again() {
+        let src = include_str!("../../nfa_complex.txt");
+        let nfa = nfa::Info::new(parse(src), src).unwrap();
+
+        let once = determinize(&nfa).to_string();
+        let again = determinize(&nfa::Info::new(parse(&once), &once).unwrap()).to_string();
+        assert!(again.contains("{{"));
+
+        let back = nfa::Info::new(parse(&again), &again).unwrap();
+        assert_eq!(equivalence::compare(&nfa, &back), Equivalence::Equivalent);
+    }
+
+    #[test]
+    fn keeps_a_dead_state_to_be_complete() {
+        let src = "
+[states]
+q0, q1
+[alphabet]
+a, b
+[initial]
+q0
+[final]
+q1
+[transitions]
+q0(a) => (q1)
+";
+        let dfa = determinize(&nfa::Info::new(parse(src), src).unwrap());
+
+        assert_eq!(dfa.table.states().count(), 3);
+        assert_eq!(dfa.transitions.len(), 3 * 2);
+    }
+}
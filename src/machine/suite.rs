@@ -0,0 +1,92 @@
+use crate::parser::{Expectation, TestCase};
+
+use super::{Machine, Outcome};
+
+/// How a single test case went
+#[derive(Debug, Clone)]
+pub struct TestResult<'a> {
+    pub case: &'a TestCase,
+    pub outcome: Outcome,
+    /// Tape left behind by the run, when the test case checks it
+    pub tape: Option<String>,
+    pub passed: bool,
+}
+
+/// Runs every test case against the same machine
+pub fn run_tests<'a>(machine: &mut dyn Machine, cases: &'a [TestCase]) -> Vec<TestResult<'a>> {
+    cases
+        .iter()
+        .map(|case| {
+            let outcome = machine.run(&case.input);
+
+            let verdict_matches = match case.expected {
+                Expectation::Accept => outcome.accepted(),
+                Expectation::Reject => !outcome.accepted(),
+            };
+
+            // Machines without a tape can never match an expected tape
+            let (tape, tape_matches) = match &case.tape {
+                Some(expected) => match machine.tape() {
+                    Some(tape) => (Some(tape.trimmed()), tape.matches(expected)),
+                    None => (None, false),
+                },
+                None => (None, true),
+            };
+
+            TestResult {
+                case,
+                outcome,
+                tape,
+                passed: verdict_matches && tape_matches,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{dfa, parse, tm};
+    use crate::parser::Parser;
+
+    fn test_file(src: &str) -> Vec<TestCase> {
+        Parser::lex(src)
+            .and_then(|tokens| Parser::parse_test_file(src, tokens))
+            .unwrap()
+    }
+
+    #[test]
+    fn runs_a_test_file_against_the_machine() {
+        let src = include_str!("../../tm_unary_addition.txt");
+        let mut machine = tm::Machine::new(tm::Info::new(parse(src), src).unwrap());
+        let cases = test_file(include_str!("../../tm_unary_addition_tests.txt"));
+
+        let results = run_tests(&mut machine, &cases);
+        assert_eq!(results.len(), 8);
+        assert!(results.iter().all(|result| result.passed));
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        let src = include_str!("../../tm_unary_addition.txt");
+        let mut machine = tm::Machine::new(tm::Info::new(parse(src), src).unwrap());
+        let cases = test_file("1+1 => REJECT\n1+1 => ACCEPT 111\n1+1 => ACCEPT 11\n");
+
+        let results = run_tests(&mut machine, &cases);
+        let passed: Vec<bool> = results.iter().map(|result| result.passed).collect();
+        assert_eq!(passed, [false, false, true]);
+        assert_eq!(results[1].tape.as_deref(), Some("11"));
+    }
+
+    #[test]
+    fn expected_tapes_fail_without_a_tape() {
+        let src = include_str!("../../dfa.txt");
+        let mut machine = dfa::Machine::new(dfa::Info::new(parse(src), src).unwrap());
+        let cases = test_file("1 => ACCEPT _\n");
+
+        let results = run_tests(&mut machine, &cases);
+        assert!(results[0].outcome.accepted());
+        assert!(!results[0].passed);
+        assert_eq!(results[0].tape, None);
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet};
+
+/// Interned name of a state, only meaningful together with the [`SymbolTable`] that
+/// handed it out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StateId(u32);
+
+impl StateId {
+    /// Position of the state in declaration order
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Interned name of an input, stack or tape symbol, only meaningful together with the
+/// [`SymbolTable`] that handed it out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(u32);
+
+impl SymbolId {
+    /// The empty string, interned by every table before anything else
+    pub const EPSILON: SymbolId = SymbolId(0);
+}
+
+/// Owns the names of every state and symbol of a machine, so the machine itself only
+/// has to store small integer ids and doesn't borrow from the source it was built from.
+///
+/// Ids are handed out in insertion order, which means sorting them gives back the order
+/// in which they were declared.
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    states: Vec<String>,
+    state_ids: HashMap<String, StateId>,
+    symbols: Vec<String>,
+    symbol_ids: HashMap<String, SymbolId>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        let mut table = Self {
+            states: vec![],
+            state_ids: HashMap::new(),
+            symbols: vec![],
+            symbol_ids: HashMap::new(),
+        };
+
+        table.intern_symbol("ε");
+        table
+    }
+
+    pub fn intern_state(&mut self, name: &str) -> StateId {
+        if let Some(&id) = self.state_ids.get(name) {
+            return id;
+        }
+
+        let id = StateId(self.states.len() as u32);
+        self.states.push(name.to_string());
+        self.state_ids.insert(name.to_string(), id);
+        id
+    }
+
+    /// Interns a new state named `name`, adding underscores until the name doesn't
+    /// clash with an existing state
+    pub fn fresh_state(&mut self, name: &str) -> StateId {
+        let mut name = name.to_string();
+        while self.state_ids.contains_key(&name) {
+            name.push('_');
+        }
+        self.intern_state(&name)
+    }
+
+    /// Interns a new symbol named `name`, adding underscores until the name doesn't
+    /// clash with an existing symbol
+    pub fn fresh_symbol(&mut self, name: &str) -> SymbolId {
+        let mut name = name.to_string();
+        while self.symbol_ids.contains_key(&name) {
+            name.push('_');
+        }
+        self.intern_symbol(&name)
+    }
+
+    pub fn intern_symbol(&mut self, name: &str) -> SymbolId {
+        if let Some(&id) = self.symbol_ids.get(name) {
+            return id;
+        }
+
+        let id = SymbolId(self.symbols.len() as u32);
+        self.symbols.push(name.to_string());
+        self.symbol_ids.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn state(&self, name: &str) -> Option<StateId> {
+        self.state_ids.get(name).copied()
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<SymbolId> {
+        self.symbol_ids.get(name).copied()
+    }
+
+    /// Looks up the symbol spelled by a single input character
+    pub fn char_symbol(&self, c: char) -> Option<SymbolId> {
+        let mut buf = [0; 4];
+        self.symbol(c.encode_utf8(&mut buf))
+    }
+
+    /// Every state, in declaration order
+    pub fn states(&self) -> impl Iterator<Item = StateId> + use<> {
+        (0..self.states.len() as u32).map(StateId)
+    }
+
+    pub fn state_name(&self, id: StateId) -> &str {
+        &self.states[id.0 as usize]
+    }
+
+    pub fn symbol_name(&self, id: SymbolId) -> &str {
+        &self.symbols[id.0 as usize]
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Names of the symbols of several machines, given as their table and alphabet. Those
+/// of the first machine come first, each machine's in declaration order, every name
+/// once and epsilon left out
+pub fn shared_alphabet<'a>(
+    machines: impl IntoIterator<Item = (&'a SymbolTable, &'a HashSet<SymbolId>)>,
+) -> Vec<String> {
+    let mut alphabet: Vec<String> = vec![];
+    for (table, symbols) in machines {
+        let mut symbols: Vec<SymbolId> = symbols
+            .iter()
+            .copied()
+            .filter(|&symbol| symbol != SymbolId::EPSILON)
+            .collect();
+        symbols.sort_unstable();
+        for symbol in symbols {
+            let name = table.symbol_name(symbol);
+            if !alphabet.iter().any(|known| known == name) {
+                alphabet.push(name.to_string());
+            }
+        }
+    }
+    alphabet
+}
@@ -1,12 +1,15 @@
 use miette::{Diagnostic, SourceSpan};
-use std::{
-    collections::{HashMap, HashSet},
-    fmt,
-};
+use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
+use crate::diagram::Diagram;
 use crate::parser::{Direction as ParserDirection, PartialMachineInfo, StackTransition};
 
+use super::{
+    Configuration, Rejection, Step, TapeContents, Verdict,
+    symbols::{StateId, SymbolId, SymbolTable},
+};
+
 #[derive(Error, Diagnostic, Debug)]
 pub enum InfoError {
     #[error("Unknown state")]
@@ -28,8 +31,8 @@ pub enum InfoError {
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct TMTransitionFrom {
-    pub initial: &'static str,
-    pub with_symbol: &'static str,
+    pub initial: StateId,
+    pub with_symbol: SymbolId,
 }
 
 #[derive(Debug, Clone)]
@@ -40,38 +43,40 @@ pub enum Direction {
 
 #[derive(Debug, Clone)]
 pub struct TMTransitionTo {
-    pub state: &'static str,
-    pub write_symbol: &'static str,
+    pub state: StateId,
+    pub write_symbol: SymbolId,
     pub direction: Direction,
 }
 
 #[derive(Clone)]
 pub struct Info {
-    pub alphabet: HashSet<&'static str>,
+    pub table: SymbolTable,
+    pub alphabet: HashSet<SymbolId>,
+    pub tape_alphabet: HashSet<SymbolId>,
     pub transitions: HashMap<TMTransitionFrom, TMTransitionTo>,
-    pub start_state: &'static str,
-    pub final_states: HashSet<&'static str>,
-    pub blank_symbol: &'static str,
+    pub start_state: StateId,
+    pub final_states: HashSet<StateId>,
+    pub blank_symbol: SymbolId,
 }
 
 impl Info {
-    pub fn new(machine_info: PartialMachineInfo, src: &'static str) -> miette::Result<Self> {
-        let states: HashSet<&'static str> = machine_info
-            .states
-            .iter()
-            .map(|state| state.src(src))
-            .collect();
+    pub fn new(machine_info: PartialMachineInfo, src: &str) -> miette::Result<Self> {
+        let mut table = SymbolTable::new();
 
-        let alphabet: HashSet<&'static str> = machine_info
+        for state in &machine_info.states {
+            table.intern_state(state.src(src));
+        }
+
+        let alphabet: HashSet<SymbolId> = machine_info
             .alphabet
             .iter()
-            .map(|symbol| symbol.src(src))
+            .map(|symbol| table.intern_symbol(symbol.src(src)))
             .collect();
 
         let tape_alphabet = match machine_info.tape_alphabet {
             Some(ref symbols) => symbols
                 .iter()
-                .map(|symbol| symbol.src(src))
+                .map(|symbol| table.intern_symbol(symbol.src(src)))
                 .collect::<HashSet<_>>(),
             None => {
                 return Err(InfoError::MissingSection {
@@ -83,7 +88,7 @@ impl Info {
 
         // Blank symbol is required for Turing Machine
         let blank_symbol = match machine_info.blank_symbol {
-            Some(ref token) => token.src(src),
+            Some(ref token) => table.intern_symbol(token.src(src)),
             None => {
                 return Err(InfoError::MissingSection {
                     section: "blank_symbol",
@@ -92,76 +97,65 @@ impl Info {
             }
         };
 
-        let final_states: HashSet<&'static str> = machine_info
-            .final_states
-            .iter()
-            .map(|state| state.src(src))
-            .collect();
-
         // Validate that all final states are in the set of states
-        for final_state in &final_states {
-            if !states.contains(final_state) {
-                for token in &machine_info.final_states {
-                    if token.src(src) == *final_state {
-                        return Err(InfoError::UnknownState { at: token.span() }.into());
-                    }
-                }
-            }
+        let mut final_states = HashSet::new();
+        for token in &machine_info.final_states {
+            let Some(state) = table.state(token.src(src)) else {
+                return Err(InfoError::UnknownState { at: token.span() }.into());
+            };
+
+            final_states.insert(state);
         }
 
-        let start_state = machine_info.start_state.src(src);
-        if !states.contains(start_state) {
+        let Some(start_state) = table.state(machine_info.start_state.src(src)) else {
             return Err(InfoError::UnknownState {
                 at: machine_info.start_state.span(),
             }
             .into());
-        }
+        };
 
         let mut transitions = HashMap::new();
 
         for transition in machine_info.transitions {
-            let from_state = transition.from.initial.src(src);
-            if !states.contains(from_state) {
+            let Some(from_state) = table.state(transition.from.initial.src(src)) else {
                 return Err(InfoError::UnknownState {
                     at: transition.from.initial.span(),
                 }
                 .into());
-            }
+            };
 
-            let with_symbol = transition.from.with_symbol.src(src);
-            if with_symbol != "ε" && !tape_alphabet.contains(with_symbol) {
+            let Some(with_symbol) = table
+                .symbol(transition.from.with_symbol.src(src))
+                .filter(|symbol| *symbol == SymbolId::EPSILON || tape_alphabet.contains(symbol))
+            else {
                 return Err(InfoError::UnknownTapeSymbol {
                     at: transition.from.with_symbol.span(),
                 }
                 .into());
-            }
+            };
 
-            let to_state = transition.to.0.src(src);
-            if !states.contains(to_state) {
+            let Some(to_state) = table.state(transition.to.0.src(src)) else {
                 return Err(InfoError::UnknownState {
                     at: transition.to.0.span(),
                 }
                 .into());
-            }
+            };
 
             // Parse tape operation data
             let write_symbol = match &transition.to.1 {
-                Some(action) => match action {
-                    StackTransition::Write(_, symbol_token) => {
-                        let symbol = symbol_token.src(src);
-                        if !tape_alphabet.contains(symbol) {
-                            return Err(InfoError::UnknownTapeSymbol {
-                                at: symbol_token.span(),
-                            }
-                            .into());
+                Some(StackTransition::Write(_, symbol_token)) => {
+                    let Some(symbol) = table
+                        .symbol(symbol_token.src(src))
+                        .filter(|symbol| tape_alphabet.contains(symbol))
+                    else {
+                        return Err(InfoError::UnknownTapeSymbol {
+                            at: symbol_token.span(),
                         }
-                        symbol
-                    }
-                    _ => {
-                        return Err(InfoError::MissingTapeOperation.into());
-                    }
-                },
-                None => {
+                        .into());
+                    };
+                    symbol
+                }
+                _ => {
                     return Err(InfoError::MissingTapeOperation.into());
                 }
             };
@@ -194,23 +188,49 @@ impl Info {
         }
 
         Ok(Self {
+            table,
             alphabet,
+            tape_alphabet,
             transitions,
             start_state,
             final_states,
             blank_symbol,
         })
     }
+
+    /// State diagram of the TM, edges are labelled `read / write, direction`
+    pub fn diagram(&self) -> Diagram {
+        let mut diagram = Diagram::new(&self.table, self.start_state, &self.final_states);
+
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by_key(|(from, _)| (from.initial, from.with_symbol));
+
+        for (from, to) in transitions {
+            let direction = match to.direction {
+                Direction::Left => "L",
+                Direction::Right => "R",
+            };
+            let label = format!(
+                "{} / {}, {direction}",
+                self.table.symbol_name(from.with_symbol),
+                self.table.symbol_name(to.write_symbol)
+            );
+            diagram.add_edge(from.initial, to.state, label);
+        }
+
+        diagram
+    }
 }
 
+#[derive(Clone)]
 struct Tape {
-    tape: Vec<&'static str>,
+    tape: Vec<SymbolId>,
     position: usize,
-    blank_symbol: &'static str,
+    blank_symbol: SymbolId,
 }
 
 impl Tape {
-    fn new(blank_symbol: &'static str) -> Self {
+    fn new(blank_symbol: SymbolId) -> Self {
         Self {
             tape: vec![blank_symbol],
             position: 0,
@@ -218,7 +238,7 @@ impl Tape {
         }
     }
 
-    fn get_current_symbol(&self) -> &'static str {
+    fn get_current_symbol(&self) -> SymbolId {
         if self.position < self.tape.len() {
             self.tape[self.position]
         } else {
@@ -226,7 +246,7 @@ impl Tape {
         }
     }
 
-    fn write_symbol(&mut self, symbol: &'static str) {
+    fn write_symbol(&mut self, symbol: SymbolId) {
         if self.position >= self.tape.len() {
             self.tape.resize(self.position + 1, self.blank_symbol);
         }
@@ -250,108 +270,129 @@ impl Tape {
     }
 }
 
-impl fmt::Debug for Tape {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Tape [")?;
-        for (index, symbol) in self.tape.iter().enumerate() {
-            if index == self.position {
-                write!(f, "[{}]", symbol)?;
-            } else {
-                write!(f, " {} ", symbol)?;
-            }
-        }
-        write!(f, "]")?;
-        Ok(())
-    }
-}
-
+#[derive(Clone)]
 pub struct Machine {
     info: Info,
+    current_state: StateId,
+    tape: Tape,
+    /// First input character that isn't a tape symbol, if any
+    unknown_symbol: Option<char>,
 }
 
 impl Machine {
     pub fn new(info: Info) -> Self {
-        Machine { info }
+        Machine {
+            current_state: info.start_state,
+            tape: Tape::new(info.blank_symbol),
+            unknown_symbol: None,
+            info,
+        }
     }
+}
 
-    pub fn run(&self, input: &str) -> bool {
-        let input_symbols: Vec<&'static str> = input
-            .chars()
-            .map(|c| {
-                let s = String::from(c).leak();
-                if !self.info.alphabet.contains(s) && s != "ε" {
-                    println!("Warning: Symbol {} i§s not in the alphabet", s);
-                }
-                &*s
-            })
-            .collect();
+impl super::Machine for Machine {
+    fn build(machine: PartialMachineInfo, src: &str) -> miette::Result<Self> {
+        Ok(Self::new(Info::new(machine, src)?))
+    }
 
-        let mut tape = Tape::new(self.info.blank_symbol);
+    fn clone_box(&self) -> Box<dyn super::Machine> {
+        Box::new(self.clone())
+    }
+
+    fn reset(&mut self, input: &str) {
+        self.tape = Tape::new(self.info.blank_symbol);
+        self.unknown_symbol = None;
 
         // Initialize tape with input
-        for symbol in input_symbols {
-            tape.write_symbol(symbol);
-            tape.move_right();
+        for c in input.chars() {
+            let symbol = self
+                .info
+                .table
+                .char_symbol(c)
+                .filter(|symbol| self.info.tape_alphabet.contains(symbol));
+
+            match symbol {
+                Some(symbol) => self.tape.write_symbol(symbol),
+                None => {
+                    self.unknown_symbol.get_or_insert(c);
+                }
+            }
+            self.tape.move_right();
         }
 
         // Reset position to beginning
-        while tape.position > 0 {
-            tape.move_left();
+        while self.tape.position > 0 {
+            self.tape.move_left();
         }
 
-        let mut current_state = self.info.start_state;
-        println!("Starting simulation with state: {}", current_state);
+        self.current_state = self.info.start_state;
+    }
 
-        // Run until we reach a final state or get stuck
-        loop {
-            let current_symbol = tape.get_current_symbol();
-            let transition_key = TMTransitionFrom {
-                initial: current_state,
-                with_symbol: current_symbol,
-            };
+    fn step(&mut self) -> Step {
+        // Symbols outside of the tape alphabet can never be read
+        if let Some(c) = self.unknown_symbol {
+            return Step::Halt(Verdict::Rejected(Rejection::UnknownSymbol { symbol: c }));
+        }
 
-            if let Some(transition) = self.info.transitions.get(&transition_key) {
-                println!(
-                    "Transition: ({}, {}) -> ({}, {}, {:?})",
-                    current_state,
-                    current_symbol,
-                    transition.state,
-                    transition.write_symbol,
-                    transition.direction
-                );
-
-                // Update the tape
-                tape.write_symbol(transition.write_symbol);
-
-                // Move the tape head
-                match transition.direction {
-                    Direction::Left => tape.move_left(),
-                    Direction::Right => tape.move_right(),
-                }
+        // Reaching a final state halts the machine
+        if self.info.final_states.contains(&self.current_state) {
+            return Step::Halt(Verdict::Accepted);
+        }
 
-                // Update current state
-                current_state = transition.state;
-                println!(
-                    "Current state: {}, {}",
-                    current_state,
-                    tape.get_current_symbol()
-                );
-                println!("{:?}", tape);
-
-                // Check if we've reached a final state
-                if self.info.final_states.contains(current_state) {
-                    println!("Reached final state: {}", current_state);
-                    return true;
-                }
-            } else {
-                println!(
-                    "No valid transition from state {} with symbol {}",
-                    current_state, current_symbol
-                );
-                break;
-            }
+        let current_symbol = self.tape.get_current_symbol();
+        let transition_key = TMTransitionFrom {
+            initial: self.current_state,
+            with_symbol: current_symbol,
+        };
+
+        let Some(transition) = self.info.transitions.get(&transition_key) else {
+            return Step::Halt(Verdict::Rejected(Rejection::NoTransition {
+                state: self.info.table.state_name(self.current_state).to_string(),
+                symbol: self.info.table.symbol_name(current_symbol).to_string(),
+                stack_top: None,
+            }));
+        };
+
+        // Update the tape
+        self.tape.write_symbol(transition.write_symbol);
+
+        // Move the tape head
+        match transition.direction {
+            Direction::Left => self.tape.move_left(),
+            Direction::Right => self.tape.move_right(),
         }
 
-        false
+        // Update current state
+        self.current_state = transition.state;
+        Step::Continue
+    }
+
+    fn tape(&self) -> Option<TapeContents> {
+        let table = &self.info.table;
+
+        Some(TapeContents {
+            cells: self
+                .tape
+                .tape
+                .iter()
+                .map(|&symbol| table.symbol_name(symbol).to_string())
+                .collect(),
+            blank: table.symbol_name(self.info.blank_symbol).to_string(),
+        })
+    }
+
+    fn configuration(&self) -> Configuration {
+        let table = &self.info.table;
+
+        Configuration::Tm {
+            state: table.state_name(self.current_state).to_string(),
+            tape: self
+                .tape
+                .tape
+                .iter()
+                .map(|&symbol| table.symbol_name(symbol).to_string())
+                .collect(),
+            head: self.tape.position,
+        }
     }
 }
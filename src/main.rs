@@ -1,11 +1,35 @@
-mod machine;
-mod parser;
+use std::process::ExitCode;
+
+use fla::{
+    lint,
+    machine::{
+        Configuration, Machine, MachineKind, Outcome, Rejection, Verdict, analysis, closure, dfa,
+        elimination, enumerate,
+        equivalence::{self, Equivalence},
+        grammar::Grammar,
+        minimize, nfa, pda, product,
+        regex::Regex,
+        subset, suite,
+    },
+    parser,
+};
+use serde::Serialize;
 
 #[derive(clap::Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
-    /// File path describing the DFA machine
-    machine_file_path: String,
+    /// File path describing the machine, or the grammar for `cfg-to-pda`. Every command
+    /// but `regex` needs one
+    machine_file_path: Option<String>,
+    /// Print every configuration the machine goes through
+    #[arg(long, global = true)]
+    trace: bool,
+    /// How results are printed
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// How a PDA accepts, overriding its `[acceptance]` section
+    #[arg(long, global = true, value_enum)]
+    acceptance: Option<AcceptanceMode>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -32,80 +56,933 @@ enum Commands {
         /// Input string to be processed by the Turing Machine
         input: String,
     },
+    /// Run the machine as the kind declared in its `[type]` section, or the most
+    /// specific kind able to run it
+    Run {
+        /// Input string to be processed by the machine
+        input: String,
+    },
+    /// Run every `input => ACCEPT|REJECT [tape]` line of a test file, or of the machine's
+    /// own `[tests]` section, against the machine
+    Test {
+        /// File path listing the test cases, defaults to the `[tests]` section
+        tests_file_path: Option<String>,
+    },
+    /// Export the state diagram of the machine, as the kind `run` would pick
+    #[command(group(clap::ArgGroup::new("output").required(true)))]
+    Export {
+        /// Print a Graphviz DOT graph
+        #[arg(long, group = "output")]
+        dot: bool,
+        /// Print an SVG picture of the diagram
+        #[arg(long, group = "output")]
+        svg: bool,
+        /// Highlight the states and transitions the machine goes through on this input
+        #[arg(long, requires = "svg", value_name = "INPUT")]
+        highlight: Option<String>,
+    },
+    /// Compute the minimal DFA equivalent to the machine, and report which states were
+    /// merged
+    Minimize {
+        /// Write the minimal DFA to this file
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Convert the NFA into an equivalent DFA, with states named after the sets of NFA
+    /// states they stand for
+    NfaToDfa {
+        /// Write the DFA to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Give a regular expression for the language of the DFA or NFA
+    ToRegex,
+    /// Check whether two DFAs or NFAs accept the same language, and give a shortest
+    /// string telling them apart when they don't
+    Equiv {
+        /// File path describing the machine to compare against
+        other_machine_file_path: String,
+    },
+    /// Tell whether the language of the DFA or NFA is empty, finite or universal, and
+    /// count the strings it accepts of each length
+    Analyze {
+        /// Count accepted strings up to this length
+        #[arg(long, default_value_t = 10)]
+        max_length: usize,
+    },
+    /// List the first strings the machine accepts, shortest first
+    Enumerate {
+        /// Number of strings to list
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// Only look at strings up to this length
+        #[arg(long, default_value_t = 10)]
+        max_len: usize,
+        /// Give up on a PDA or Turing machine run after this many steps
+        #[arg(long, default_value_t = 1000)]
+        max_steps: usize,
+    },
+    /// Warn about unreachable or dead states and unused symbols, also shown before
+    /// every other command
+    Lint,
+    /// Convert the PDA into one accepting the same language under another acceptance mode
+    ConvertAcceptance {
+        /// Acceptance mode of the new PDA
+        #[arg(value_enum)]
+        mode: AcceptanceMode,
+        /// Write the PDA to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Convert a context-free grammar file into a single state PDA accepting by empty stack
+    CfgToPda {
+        /// Write the PDA to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Combine two DFAs into one with the product construction
+    Combine {
+        /// Which strings the combined DFA accepts
+        #[arg(value_enum)]
+        operation: Operation,
+        /// File path describing the second DFA
+        other_machine_file_path: String,
+        /// Write the DFA to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Build the DFA accepting exactly the strings the DFA rejects
+    Complement {
+        /// Write the DFA to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Build the NFA accepting the strings of either NFA, joined with epsilon moves
+    Union {
+        /// File path describing the second NFA
+        other_machine_file_path: String,
+        /// Write the NFA to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Build the NFA accepting a string of the NFA followed by a string of another one
+    Concat {
+        /// File path describing the NFA reading the end of the strings
+        other_machine_file_path: String,
+        /// Write the NFA to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Build the NFA accepting any number of strings of the NFA in a row (Kleene star)
+    Star {
+        /// Write the NFA to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Build the NFA accepting the strings of the NFA spelled backwards
+    Reverse {
+        /// Write the NFA to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Build an ε-NFA from a regular expression, no machine file needed
+    Regex {
+        /// Regular expression using |, *, +, ?, parentheses, [classes], . and ε
+        pattern: String,
+        /// Alphabet of the NFA, defaults to the symbols used in the pattern
+        #[arg(long, value_delimiter = ',')]
+        alphabet: Option<Vec<String>>,
+        /// Write the NFA to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<String>,
+    },
 }
 
-// Could make Machines a trait and use dynamic dispatch here
-fn handle_cli(cli: Cli, src: &'static str) -> miette::Result<()> {
-    let lexed = parser::Parser::lex(src)?;
-    let parsed = parser::Parser::parse(src, lexed)?;
-    match cli.command {
-        Commands::Dfa { input } => {
-            use machine::dfa;
-            let dfa_info = dfa::Info::new(parsed, src)?;
-            let dfa = dfa::Machine::new(dfa_info);
-            let accepted = dfa.run(&input);
-
-            if accepted {
-                println!("Input is ACCEPTED");
-            } else {
-                println!("Input is REJECTED");
-            }
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    /// Human readable output
+    Text,
+    /// A single JSON document on stdout
+    Json,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum AcceptanceMode {
+    /// In a final state once the input is read
+    FinalState,
+    /// With an empty stack once the input is read
+    EmptyStack,
+    /// In a final state with an empty stack once the input is read
+    Both,
+}
+
+impl From<AcceptanceMode> for pda::Acceptance {
+    fn from(mode: AcceptanceMode) -> Self {
+        match mode {
+            AcceptanceMode::FinalState => pda::Acceptance::FinalState,
+            AcceptanceMode::EmptyStack => pda::Acceptance::EmptyStack,
+            AcceptanceMode::Both => pda::Acceptance::Both,
         }
-        Commands::Nfa { input } => {
-            use machine::nfa;
-            let nfa_info = nfa::Info::new(parsed, src)?;
-            let nfa = nfa::Machine::new(nfa_info);
-            let accepted = nfa.run(&input);
+    }
+}
 
-            if accepted {
-                println!("Input is ACCEPTED");
-            } else {
-                println!("Input is REJECTED");
-            }
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Operation {
+    /// Strings accepted by either DFA
+    Union,
+    /// Strings accepted by both DFAs
+    Intersection,
+    /// Strings accepted by the first DFA but not the second
+    Difference,
+    /// Strings accepted by exactly one of the DFAs
+    SymmetricDifference,
+}
+
+impl From<Operation> for product::Operation {
+    fn from(operation: Operation) -> Self {
+        match operation {
+            Operation::Union => product::Operation::Union,
+            Operation::Intersection => product::Operation::Intersection,
+            Operation::Difference => product::Operation::Difference,
+            Operation::SymmetricDifference => product::Operation::SymmetricDifference,
         }
-        Commands::Pda { input } => {
-            use machine::pda;
-            let pda_info = pda::Info::new(parsed, src)?;
-            let pda = pda::Machine::new(pda_info);
-            let accepted = pda.run(&input);
+    }
+}
 
-            if accepted {
-                println!("Input is ACCEPTED");
-            } else {
-                println!("Input is REJECTED");
-            }
+/// JSON shape of a lint warning
+#[derive(Serialize)]
+struct LintReport {
+    message: String,
+    /// Byte offset and length of the offending name in the machine file
+    offset: usize,
+    length: usize,
+}
+
+/// JSON shape of an equivalence check
+#[derive(Serialize)]
+struct EquivalenceReport<'a> {
+    equivalent: bool,
+    /// Shortest string accepted by only one of the machines
+    witness: Option<&'a str>,
+    /// Path of the machine accepting the witness
+    accepted_by: Option<&'a str>,
+}
+
+/// JSON shape of a finished run
+#[derive(Serialize)]
+struct RunReport<'a> {
+    machine: String,
+    input: &'a str,
+    accepted: bool,
+    steps: usize,
+    final_configuration: &'a Configuration,
+    rejection: Option<RejectionReport<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace: Option<&'a [Configuration]>,
+}
+
+#[derive(Serialize)]
+struct RejectionReport<'a> {
+    /// Same sentence the text output prints
+    reason: String,
+    #[serde(flatten)]
+    details: &'a Rejection,
+}
+
+impl<'a> RejectionReport<'a> {
+    fn new(outcome: &'a Outcome) -> Option<Self> {
+        match &outcome.verdict {
+            Verdict::Accepted => None,
+            Verdict::Rejected(rejection) => Some(Self {
+                reason: rejection.to_string(),
+                details: rejection,
+            }),
         }
-        Commands::Tm { input } => {
-            use machine::tm;
-            let tm_info = tm::Info::new(parsed, src)?;
-            let tm = tm::Machine::new(tm_info);
-            let accepted = tm.run(&input);
+    }
+}
+
+/// JSON shape of a test run
+#[derive(Serialize)]
+struct TestReport<'a> {
+    machine: String,
+    passed: usize,
+    failed: usize,
+    total: usize,
+    results: Vec<TestCaseReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct TestCaseReport<'a> {
+    input: &'a str,
+    expected: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected_tape: Option<&'a str>,
+    passed: bool,
+    accepted: bool,
+    rejection: Option<RejectionReport<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tape: Option<&'a str>,
+}
+
+fn kind_and_origin(
+    parsed: &parser::PartialMachineInfo,
+    src: &str,
+) -> miette::Result<(MachineKind, &'static str)> {
+    Ok(match MachineKind::declared(parsed, src)? {
+        Some(kind) => (kind, "declared in [type]"),
+        None => (MachineKind::detect(parsed, src), "detected"),
+    })
+}
+
+/// Kind declared in the file's `[type]` section, falling back to the most specific kind
+/// able to run it
+fn resolve_kind(
+    parsed: &parser::PartialMachineInfo,
+    src: &str,
+    format: Format,
+) -> miette::Result<MachineKind> {
+    let (kind, origin) = kind_and_origin(parsed, src)?;
+
+    // The JSON report names the kind itself
+    if format == Format::Text {
+        println!("Running as {kind} ({origin})");
+    }
+
+    Ok(kind)
+}
+
+/// Builds the machine, with the PDA acceptance mode given on the command line if any
+fn build_machine(
+    kind: MachineKind,
+    parsed: parser::PartialMachineInfo,
+    src: &str,
+    acceptance: Option<AcceptanceMode>,
+) -> miette::Result<Box<dyn Machine>> {
+    match (kind, acceptance) {
+        (_, None) => kind.build(parsed, src),
+        (MachineKind::Pda, Some(acceptance)) => {
+            let mut info = pda::Info::new(parsed, src)?;
+            info.acceptance = acceptance.into();
+            Ok(Box::new(pda::Machine::new(info)))
+        }
+        (_, Some(_)) => <Cli as clap::CommandFactory>::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!("--acceptance only applies to PDAs, not to a {kind}"),
+            )
+            .exit(),
+    }
+}
+
+fn print_json(value: &impl Serialize) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("Reports always serialize")
+    );
+}
+
+fn run_tests(
+    mut parsed: parser::PartialMachineInfo,
+    src: &str,
+    tests_path: Option<String>,
+    acceptance: Option<AcceptanceMode>,
+    format: Format,
+) -> miette::Result<ExitCode> {
+    let cases = match tests_path {
+        Some(tests_path) => {
+            let tests_src =
+                std::fs::read_to_string(&tests_path).expect("Failed to open tests file");
+            parser::Parser::lex(&tests_src)
+                .and_then(|lexed| parser::Parser::parse_test_file(&tests_src, lexed))
+                .map_err(|report| {
+                    report.with_source_code(miette::NamedSource::new(tests_path, tests_src.clone()))
+                })?
+        }
+        None => parsed
+            .tests
+            .take()
+            .ok_or(parser::ParserError::MissingSection { section: "tests" })?,
+    };
+
+    let kind = resolve_kind(&parsed, src, format)?;
+    let mut machine = build_machine(kind, parsed, src, acceptance)?;
+    let results = suite::run_tests(machine.as_mut(), &cases);
+    let failed = results.iter().filter(|result| !result.passed).count();
+    let exit_code = if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    };
+
+    if format == Format::Json {
+        print_json(&TestReport {
+            machine: kind.to_string(),
+            passed: results.len() - failed,
+            failed,
+            total: results.len(),
+            results: results
+                .iter()
+                .map(|result| TestCaseReport {
+                    input: &result.case.input,
+                    expected: result.case.expected.to_string(),
+                    expected_tape: result.case.tape.as_deref(),
+                    passed: result.passed,
+                    accepted: result.outcome.accepted(),
+                    rejection: RejectionReport::new(&result.outcome),
+                    tape: result.tape.as_deref(),
+                })
+                .collect(),
+        });
+        return Ok(exit_code);
+    }
+
+    for result in &results {
+        let case = result.case;
+        let expected = match &case.tape {
+            Some(tape) => format!("{} {}", case.expected, tape),
+            None => case.expected.to_string(),
+        };
+
+        if result.passed {
+            println!("PASS {:?} => {expected}", case.input);
+            continue;
+        }
+
+        let actual = match &result.outcome.verdict {
+            Verdict::Accepted => "ACCEPT".to_string(),
+            Verdict::Rejected(rejection) => format!("REJECT ({rejection})"),
+        };
+        match &result.tape {
+            Some(tape) => println!("FAIL {:?} => {expected}, got {actual} {tape}", case.input),
+            None => println!("FAIL {:?} => {expected}, got {actual}", case.input),
+        }
+    }
+
+    println!(
+        "{} passed, {} failed, {} total",
+        results.len() - failed,
+        failed,
+        results.len()
+    );
+
+    Ok(exit_code)
+}
+
+fn export(
+    parsed: parser::PartialMachineInfo,
+    src: &str,
+    dot: bool,
+    highlight: Option<String>,
+    acceptance: Option<AcceptanceMode>,
+) -> miette::Result<ExitCode> {
+    let (kind, _) = kind_and_origin(&parsed, src)?;
+    let diagram = kind.diagram(parsed.clone(), src)?;
+
+    if dot {
+        print!("{}", diagram.to_dot(&kind.to_string()));
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let path = match highlight {
+        Some(input) => {
+            let mut machine = build_machine(kind, parsed, src, acceptance)?;
+            let mut trace = vec![];
+            machine.run_traced(&input, &mut |configuration| {
+                trace.push(configuration.clone())
+            });
+            Some(diagram.run_path(&trace))
+        }
+        None => None,
+    };
+
+    print!("{}", diagram.to_svg(path.as_ref()));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Parses a machine file other than the main one, errors pointing into that file
+fn load_other(path: &str) -> miette::Result<(String, parser::PartialMachineInfo)> {
+    let src = std::fs::read_to_string(path).expect("Failed to open input file");
+    parser::Parser::lex(&src)
+        .and_then(|lexed| parser::Parser::parse(&src, lexed))
+        .map(|parsed| (src.clone(), parsed))
+        .map_err(|report| report.with_source_code(miette::NamedSource::new(path, src.clone())))
+}
+
+/// Builds the NFA described by a machine file other than the main one
+fn load_other_nfa(path: &str) -> miette::Result<nfa::Info> {
+    let (src, parsed) = load_other(path)?;
+    nfa::Info::new(parsed, &src)
+        .map_err(|report| report.with_source_code(miette::NamedSource::new(path, src.clone())))
+}
+
+fn equiv(
+    parsed: parser::PartialMachineInfo,
+    src: &str,
+    path: Option<String>,
+    other_path: String,
+    format: Format,
+) -> miette::Result<ExitCode> {
+    let path = path.unwrap_or_default();
+    let first = nfa::Info::new(parsed, src)?;
+    let second = load_other_nfa(&other_path)?;
 
-            if accepted {
-                println!("Input is ACCEPTED");
+    let (witness, accepted_by, rejected_by) = match equivalence::compare(&first, &second) {
+        Equivalence::Equivalent => (None, None, None),
+        Equivalence::Different {
+            witness,
+            accepted_by_first,
+        } => {
+            let (accepted_by, rejected_by) = if accepted_by_first {
+                (&path, &other_path)
             } else {
-                println!("Input is REJECTED");
+                (&other_path, &path)
+            };
+            (Some(witness), Some(accepted_by.as_str()), Some(rejected_by))
+        }
+    };
+
+    if format == Format::Json {
+        print_json(&EquivalenceReport {
+            equivalent: witness.is_none(),
+            witness: witness.as_deref(),
+            accepted_by,
+        });
+    } else {
+        match (&witness, accepted_by, rejected_by) {
+            (Some(witness), Some(accepted_by), Some(rejected_by)) => {
+                let witness = describe_string(witness);
+                println!("The machines accept different languages");
+                println!("{accepted_by} accepts {witness}, {rejected_by} rejects it");
             }
+            _ => println!("The machines accept the same language"),
+        }
+    }
+
+    Ok(if witness.is_none() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Spells out a string for text output, the empty one included
+fn describe_string(string: &str) -> String {
+    if string.is_empty() {
+        "the empty string".to_string()
+    } else {
+        format!("{string:?}")
+    }
+}
+
+fn analyze(
+    parsed: parser::PartialMachineInfo,
+    src: &str,
+    max_length: usize,
+    format: Format,
+) -> miette::Result<ExitCode> {
+    let dfa = subset::determinize(&nfa::Info::new(parsed, src)?);
+    let analysis = analysis::analyze(&dfa, max_length);
+
+    if format == Format::Json {
+        print_json(&analysis);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let size = match (analysis.empty, analysis.finite) {
+        (true, _) => "empty",
+        (false, true) => "finite",
+        (false, false) => "infinite",
+    };
+    println!("The language is {size}");
+    if analysis.universal {
+        println!("Every string over the alphabet is accepted");
+    }
+    if let Some(string) = &analysis.shortest_accepted {
+        println!("Shortest accepted string: {}", describe_string(string));
+    }
+    if let Some(string) = &analysis.shortest_rejected {
+        println!("Shortest rejected string: {}", describe_string(string));
+    }
+
+    println!("Accepted strings by length:");
+    for (length, count) in analysis.accepted_by_length.iter().enumerate() {
+        println!("  {length:>3}: {count}");
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn enumerate(
+    parsed: parser::PartialMachineInfo,
+    src: &str,
+    limit: usize,
+    max_len: usize,
+    max_steps: usize,
+    acceptance: Option<AcceptanceMode>,
+    format: Format,
+) -> miette::Result<ExitCode> {
+    let enumeration = match resolve_kind(&parsed, src, format)? {
+        MachineKind::Dfa | MachineKind::Nfa => {
+            let dfa = subset::determinize(&nfa::Info::new(parsed, src)?);
+            enumerate::finite_automaton(&dfa, limit, max_len)
+        }
+        kind @ (MachineKind::Pda | MachineKind::Tm) => {
+            let alphabet: Vec<String> = parsed
+                .alphabet
+                .iter()
+                .map(|symbol| symbol.src(src).to_string())
+                .collect();
+            let machine = build_machine(kind, parsed, src, acceptance)?;
+            enumerate::dovetail(&alphabet, limit, max_len, max_steps, machine.as_ref())
+        }
+    };
+
+    if format == Format::Json {
+        print_json(&enumeration);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for string in &enumeration.accepted {
+        println!("{}", if string.is_empty() { "ε" } else { string });
+    }
+    if enumeration.accepted.is_empty() {
+        println!("No accepted string of at most {max_len} symbols");
+    }
+    if !enumeration.undecided.is_empty() {
+        let strings: Vec<&str> = enumeration
+            .undecided
+            .iter()
+            .map(|string| if string.is_empty() { "ε" } else { string })
+            .collect();
+        println!(
+            "Still running after {max_steps} steps, so left out: {}",
+            strings.join(", ")
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Writes a generated machine file to `output`, or prints it when there is none
+fn write_machine(machine: &str, output: Option<String>) {
+    match output {
+        Some(path) => {
+            std::fs::write(&path, machine).expect("Failed to write output file");
+            println!("Written to {path}");
         }
+        None => print!("{machine}"),
     }
+}
+
+fn minimize(
+    parsed: parser::PartialMachineInfo,
+    src: &str,
+    output: Option<String>,
+) -> miette::Result<ExitCode> {
+    let info = dfa::Info::new(parsed, src)?;
+    let minimized = minimize::minimize(&info);
 
-    Ok(())
+    println!(
+        "Minimal DFA keeps {} of {} states",
+        minimized.classes.len(),
+        info.table.states().count()
+    );
+    for class in &minimized.classes {
+        let names: Vec<&str> = class
+            .iter()
+            .map(|&state| info.table.state_name(state))
+            .collect();
+        println!("{} <- {}", names[0], names.join(", "));
+    }
+
+    if !minimized.unreachable.is_empty() {
+        let names: Vec<&str> = minimized
+            .unreachable
+            .iter()
+            .map(|&state| info.table.state_name(state))
+            .collect();
+        println!("Dropped unreachable states: {}", names.join(", "));
+    }
+
+    if let Some(path) = output {
+        std::fs::write(&path, minimized.dfa.to_string()).expect("Failed to write output file");
+        println!("Minimal DFA written to {path}");
+    }
+
+    Ok(ExitCode::SUCCESS)
 }
 
-fn main() -> miette::Result<()> {
+/// Prints lint warnings to stderr, pointing into the machine file
+fn print_lints(lints: Vec<lint::Lint>, path: &str, src: &str) {
+    for lint in lints {
+        let report = miette::Report::new(lint)
+            .with_source_code(miette::NamedSource::new(path, src.to_string()));
+        eprintln!("{report:?}");
+    }
+}
+
+fn lint(lints: Vec<lint::Lint>, path: &str, src: &str, format: Format) -> ExitCode {
+    let exit_code = if lints.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    };
+
+    if format == Format::Json {
+        let reports: Vec<LintReport> = lints
+            .iter()
+            .map(|lint| {
+                let span = miette::Diagnostic::labels(lint)
+                    .and_then(|mut labels| labels.next())
+                    .expect("every lint points at a name");
+                LintReport {
+                    message: lint.to_string(),
+                    offset: span.offset(),
+                    length: span.len(),
+                }
+            })
+            .collect();
+        print_json(&reports);
+        return exit_code;
+    }
+
+    match lints.len() {
+        0 => println!("No warnings"),
+        1 => println!("1 warning"),
+        count => println!("{count} warnings"),
+    }
+    print_lints(lints, path, src);
+    exit_code
+}
+
+fn handle_cli(cli: Cli, src: &str) -> miette::Result<ExitCode> {
+    let lexed = parser::Parser::lex(src)?;
+    if let Commands::CfgToPda { output } = cli.command {
+        let pda = Grammar::parse(src, lexed)?.to_pda();
+        write_machine(&pda.to_string(), output);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let parsed = parser::Parser::parse(src, lexed)?;
+
+    let path = cli.machine_file_path.clone().unwrap_or_default();
+    // Under `--acceptance empty-stack`, a PDA accepts in states that aren't final
+    let acceptance = match cli.acceptance {
+        Some(mode) => mode.into(),
+        None => pda::Acceptance::declared(&parsed, src)
+            .ok()
+            .flatten()
+            .unwrap_or_default(),
+    };
+    let lints = lint::lint(&parsed, src, acceptance);
+    if let Commands::Lint = cli.command {
+        return Ok(lint(lints, &path, src, cli.format));
+    }
+    print_lints(lints, &path, src);
+
+    let (kind, input) = match cli.command {
+        Commands::Dfa { input } => (MachineKind::Dfa, input),
+        Commands::Nfa { input } => (MachineKind::Nfa, input),
+        Commands::Pda { input } => (MachineKind::Pda, input),
+        Commands::Tm { input } => (MachineKind::Tm, input),
+        Commands::Run { input } => (resolve_kind(&parsed, src, cli.format)?, input),
+        Commands::Test { tests_file_path } => {
+            return run_tests(parsed, src, tests_file_path, cli.acceptance, cli.format);
+        }
+        Commands::Export {
+            dot,
+            svg: _,
+            highlight,
+        } => {
+            return export(parsed, src, dot, highlight, cli.acceptance);
+        }
+        Commands::Minimize { output } => {
+            return minimize(parsed, src, output);
+        }
+        Commands::NfaToDfa { output } => {
+            let dfa = subset::determinize(&nfa::Info::new(parsed, src)?);
+            write_machine(&dfa.to_string(), output);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Equiv {
+            other_machine_file_path,
+        } => {
+            return equiv(
+                parsed,
+                src,
+                cli.machine_file_path,
+                other_machine_file_path,
+                cli.format,
+            );
+        }
+        Commands::Analyze { max_length } => {
+            return analyze(parsed, src, max_length, cli.format);
+        }
+        Commands::Enumerate {
+            limit,
+            max_len,
+            max_steps,
+        } => {
+            return enumerate(
+                parsed,
+                src,
+                limit,
+                max_len,
+                max_steps,
+                cli.acceptance,
+                cli.format,
+            );
+        }
+        Commands::ConvertAcceptance { mode, output } => {
+            let pda = pda::Info::new(parsed, src)?.converted(mode.into());
+            write_machine(&pda.to_string(), output);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Combine {
+            operation,
+            other_machine_file_path,
+            output,
+        } => {
+            let first = dfa::Info::new(parsed, src)?;
+            let (other_src, other_parsed) = load_other(&other_machine_file_path)?;
+            let second = dfa::Info::new(other_parsed, &other_src).map_err(|report| {
+                report.with_source_code(miette::NamedSource::new(
+                    &other_machine_file_path,
+                    other_src.clone(),
+                ))
+            })?;
+            let dfa = product::product(&first, &second, operation.into());
+            write_machine(&dfa.to_string(), output);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Complement { output } => {
+            let dfa = product::complement(&dfa::Info::new(parsed, src)?);
+            write_machine(&dfa.to_string(), output);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Union {
+            other_machine_file_path,
+            output,
+        } => {
+            let second = load_other_nfa(&other_machine_file_path)?;
+            let nfa = closure::union(&nfa::Info::new(parsed, src)?, &second);
+            write_machine(&nfa.to_string(), output);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Concat {
+            other_machine_file_path,
+            output,
+        } => {
+            let second = load_other_nfa(&other_machine_file_path)?;
+            let nfa = closure::concat(&nfa::Info::new(parsed, src)?, &second);
+            write_machine(&nfa.to_string(), output);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Star { output } => {
+            let nfa = closure::star(&nfa::Info::new(parsed, src)?);
+            write_machine(&nfa.to_string(), output);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Reverse { output } => {
+            let nfa = closure::reverse(&nfa::Info::new(parsed, src)?);
+            write_machine(&nfa.to_string(), output);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::ToRegex => {
+            println!("{}", elimination::to_regex(&nfa::Info::new(parsed, src)?));
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Lint => unreachable!("lint returns before running anything"),
+        Commands::CfgToPda { .. } => unreachable!("handled before parsing a machine file"),
+        Commands::Regex { .. } => unreachable!("regex doesn't read a machine file"),
+    };
+
+    let mut machine = build_machine(kind, parsed, src, cli.acceptance)?;
+
+    if cli.format == Format::Json {
+        let mut trace = vec![];
+        let outcome = if cli.trace {
+            machine.run_traced(&input, &mut |configuration| {
+                trace.push(configuration.clone())
+            })
+        } else {
+            machine.run(&input)
+        };
+
+        print_json(&RunReport {
+            machine: kind.to_string(),
+            input: &input,
+            accepted: outcome.accepted(),
+            steps: outcome.steps,
+            final_configuration: &outcome.configuration,
+            rejection: RejectionReport::new(&outcome),
+            trace: cli.trace.then_some(trace.as_slice()),
+        });
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let outcome = if cli.trace {
+        let mut step = 0;
+        machine.run_traced(&input, &mut |configuration| {
+            println!("{step:>4}: {configuration}");
+            step += 1;
+        })
+    } else {
+        machine.run(&input)
+    };
+
+    println!("Final configuration: {}", outcome.configuration);
+    match outcome.verdict {
+        Verdict::Accepted => println!("Input is ACCEPTED"),
+        Verdict::Rejected(rejection) => {
+            println!("Rejected: {rejection}");
+            println!("Input is REJECTED");
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn regex_to_nfa(
+    pattern: String,
+    alphabet: Option<Vec<String>>,
+    output: Option<String>,
+) -> miette::Result<ExitCode> {
+    let regex = Regex::parse(&pattern)
+        .map_err(|error| miette::Report::new(error).with_source_code(pattern.clone()))?;
+    let nfa = regex.to_nfa(alphabet.as_deref());
+
+    write_machine(&nfa.to_string(), output);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> miette::Result<ExitCode> {
     let cli = <Cli as clap::Parser>::parse();
-    let src =
-        std::fs::read_to_string(cli.machine_file_path.clone()).expect("Failed to open input file");
-    if src.is_empty() {
-        println!("Input file is empty");
-        return Ok(());
+
+    if let Commands::Regex {
+        pattern,
+        alphabet,
+        output,
+    } = cli.command
+    {
+        return regex_to_nfa(pattern, alphabet, output);
     }
 
-    let path = cli.machine_file_path.clone();
-    let src = src.leak();
+    let Some(path) = cli.machine_file_path.clone() else {
+        <Cli as clap::CommandFactory>::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "this command needs a <MACHINE_FILE_PATH>",
+            )
+            .exit();
+    };
 
-    handle_cli(cli, src)
-        .map_err(|report| report.with_source_code(miette::NamedSource::new(path, &*src)))?;
+    let src = std::fs::read_to_string(&path).expect("Failed to open input file");
+    if src.is_empty() {
+        println!("Input file is empty");
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    Ok(())
+    handle_cli(cli, &src)
+        .map_err(|report| report.with_source_code(miette::NamedSource::new(path, src.clone())))
 }
@@ -1,4 +1,4 @@
-use std::{collections::HashSet, fmt, iter::Peekable};
+use std::{collections::HashSet, fmt, iter::Peekable, str::CharIndices};
 
 use miette::{Diagnostic, SourceSpan};
 use thiserror::Error;
@@ -55,9 +55,28 @@ pub enum ParserError {
         #[label("here")]
         at: SourceSpan,
     },
+    #[error("Unknown machine type")]
+    #[diagnostic(help("expected one of dfa, nfa, pda or tm"))]
+    UnknownMachineType {
+        #[label("here")]
+        at: SourceSpan,
+    },
+    #[error("Unknown acceptance mode")]
+    #[diagnostic(help("expected one of final_state, empty_stack or both"))]
+    UnknownAcceptanceMode {
+        #[label("here")]
+        at: SourceSpan,
+    },
+    #[error("Unknown stack semantics")]
+    #[diagnostic(help("expected one of lenient or strict"))]
+    UnknownStackSemantics {
+        #[label("here")]
+        at: SourceSpan,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TokenKind {
     LeftSquareBracket,
     RightSquareBracket,
@@ -68,6 +87,7 @@ pub enum TokenKind {
     Arrow,
     Identifier,
     Push,
+    Replace,
     Pop,
     Noop,
     Write,
@@ -87,7 +107,11 @@ impl Token {
         Self { kind, span }
     }
 
-    pub fn src(&self, src: &'static str) -> &'static str {
+    pub fn kind(&self) -> &TokenKind {
+        &self.kind
+    }
+
+    pub fn src<'a>(&self, src: &'a str) -> &'a str {
         &src[self.span.offset()..self.span.offset() + self.span.len()]
     }
 
@@ -107,7 +131,8 @@ impl fmt::Display for Token {
             TokenKind::Colon => write!(f, ":"),
             TokenKind::Arrow => write!(f, "=>"),
             TokenKind::Identifier => write!(f, "<identifier>"),
-            TokenKind::Push => write!(f, "PUSH:<symbol>"),
+            TokenKind::Push => write!(f, "PUSH:<symbols>"),
+            TokenKind::Replace => write!(f, "REPLACE:<symbols>"),
             TokenKind::Pop => write!(f, "POP"),
             TokenKind::Noop => write!(f, "NOOP"),
             TokenKind::Write => write!(f, "WRITE:<symbol>"),
@@ -133,7 +158,8 @@ pub struct TransitionFrom {
 
 #[derive(Debug, Clone)]
 pub enum StackTransition {
-    Push((), Token), // PUSH:symbol
+    Push((), Vec<Token>),    // PUSH:symbols, top first
+    Replace((), Vec<Token>), // REPLACE:symbols, top first
     Pop(()),
     NoOp(()),
     Write((), Token), // WRITE:symbol
@@ -152,13 +178,38 @@ pub struct TransitionTo(
     pub Option<Direction>,
 );
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TransitionInfo {
     pub from: TransitionFrom,
     pub to: TransitionTo,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expectation {
+    Accept,
+    Reject,
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expectation::Accept => write!(f, "ACCEPT"),
+            Expectation::Reject => write!(f, "REJECT"),
+        }
+    }
+}
+
+/// A single `input => ACCEPT|REJECT [tape]` line
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    pub input: String,
+    pub expected: Expectation,
+    /// Expected tape contents once the machine halts, for Turing machines
+    pub tape: Option<String>,
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, Clone)]
 pub struct PartialMachineInfo {
     pub states: Vec<Token>,
     pub alphabet: Vec<Token>,
@@ -171,12 +222,21 @@ pub struct PartialMachineInfo {
 
     pub tape_alphabet: Option<Vec<Token>>,
     pub blank_symbol: Option<Token>,
+
+    /// Explicit machine type from the optional `[type]` section
+    pub kind: Option<Token>,
+    /// How a PDA accepts, from the optional `[acceptance]` section
+    pub acceptance: Option<Token>,
+    /// How a PDA treats its stack, from the optional `[stack_semantics]` section
+    pub stack_semantics: Option<Token>,
+    /// Regression suite from the optional `[tests]` section
+    pub tests: Option<Vec<TestCase>>,
 }
 
 pub struct Parser;
 
 impl Parser {
-    pub fn lex(input: &'static str) -> miette::Result<Vec<Token>> {
+    pub fn lex(input: &str) -> miette::Result<Vec<Token>> {
         let mut tokens = vec![];
 
         let eof_span = SourceSpan::new(input.len().into(), 0);
@@ -234,13 +294,22 @@ impl Parser {
                         input.next();
                     }
                 }
-                // Identifiers
+                // Identifiers. Generated names may hold sets of states in braces, like
+                // `{q0,q2}_∅` or `a_{{q0},{q1}}`: braces nest and take anything up to the
+                // matching one
                 ch => {
                     let mut identifier = ch.to_string();
-                    while let Some((_, ch)) = input.peek() {
-                        if ch.is_alphanumeric() || *ch == '_' {
-                            identifier.push(*ch);
+                    if ch == '{' {
+                        Self::lex_braces(&mut input, i, &mut identifier)?;
+                    }
+                    while let Some(&(_, ch)) = input.peek() {
+                        if ch.is_alphanumeric() || ch == '_' || ch == '∅' {
+                            identifier.push(ch);
+                            input.next();
+                        } else if ch == '{' {
+                            identifier.push(ch);
                             input.next();
+                            Self::lex_braces(&mut input, i, &mut identifier)?;
                         } else {
                             break;
                         }
@@ -253,6 +322,12 @@ impl Parser {
                                 SourceSpan::new(i.into(), identifier.len()),
                             ));
                         }
+                        "REPLACE" => {
+                            tokens.push(Token::new(
+                                TokenKind::Replace,
+                                SourceSpan::new(i.into(), identifier.len()),
+                            ));
+                        }
                         "POP" => {
                             tokens.push(Token::new(
                                 TokenKind::Pop,
@@ -298,7 +373,37 @@ impl Parser {
         Ok(tokens)
     }
 
-    pub fn parse(src: &'static str, input: Vec<Token>) -> miette::Result<PartialMachineInfo> {
+    /// Reads the rest of a braced group whose `{` already ends `identifier`, up to its
+    /// matching `}`. Identifiers start at `start`
+    fn lex_braces(
+        input: &mut Peekable<CharIndices>,
+        start: usize,
+        identifier: &mut String,
+    ) -> miette::Result<()> {
+        let mut depth = 1;
+        while depth > 0 {
+            match input.next() {
+                Some((_, '\n')) | None => {
+                    return Err(LexerError::UnexpectedCharacter {
+                        at: SourceSpan::new(start.into(), identifier.len()),
+                        expected: "}",
+                    }
+                    .into());
+                }
+                Some((_, ch)) => {
+                    match ch {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    identifier.push(ch);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn parse(src: &str, input: Vec<Token>) -> miette::Result<PartialMachineInfo> {
         let mut states = None;
         let mut alphabet = None;
         let mut stack_alphabet = None;
@@ -308,9 +413,13 @@ impl Parser {
         let mut final_states = None;
         let mut tape_alphabet = None;
         let mut blank_symbol = None;
+        let mut kind = None;
+        let mut acceptance = None;
+        let mut stack_semantics = None;
+        let mut tests = None;
 
         let mut seen_sections: HashSet<Token> = HashSet::new();
-        let ref mut input = input.into_iter().peekable();
+        let input = &mut input.into_iter().peekable();
 
         loop {
             let section = Self::parse_section(input)?;
@@ -355,6 +464,18 @@ impl Parser {
                 "transitions" => {
                     transitions = Some(Parser::parse_transitions(input)?);
                 }
+                "type" => {
+                    kind = Some(Parser::parse_single_section(input)?);
+                }
+                "acceptance" => {
+                    acceptance = Some(Parser::parse_single_section(input)?);
+                }
+                "stack_semantics" => {
+                    stack_semantics = Some(Parser::parse_single_section(input)?);
+                }
+                "tests" => {
+                    tests = Some(Parser::parse_tests(src, input)?);
+                }
                 _ => {
                     return Err(ParserError::UnknownSectionName { at: section.span }.into());
                 }
@@ -381,10 +502,30 @@ impl Parser {
             start_stack,
             tape_alphabet,
             blank_symbol,
+            kind,
+            acceptance,
+            stack_semantics,
+            tests,
         })
     }
 
-    fn parse_section(
+    /// Parses a standalone file made only of test case lines
+    pub fn parse_test_file(src: &str, input: Vec<Token>) -> miette::Result<Vec<TestCase>> {
+        let input = &mut input.into_iter().peekable();
+        let tests = Self::parse_tests(src, input)?;
+
+        let token = input.next().unwrap();
+        match token.kind {
+            TokenKind::EOF => Ok(tests),
+            _ => Err(ParserError::UnexpectedToken {
+                at: token.span,
+                expected: "<test case>",
+            }
+            .into()),
+        }
+    }
+
+    pub(crate) fn parse_section(
         input: &mut Peekable<impl Iterator<Item = Token>>,
     ) -> miette::Result<Option<Token>> {
         // Assert [
@@ -446,30 +587,34 @@ impl Parser {
         Ok(Some(name))
     }
 
-    fn parse_single_section(
+    pub(crate) fn parse_single_section(
         input: &mut Peekable<impl Iterator<Item = Token>>,
     ) -> miette::Result<Token> {
         let token = input.next().unwrap();
         match token.kind {
             TokenKind::Identifier => Ok(token),
-            TokenKind::EOF => {
-                return Err(ParserError::UnexpectedEOF.into());
-            }
-            _ => {
-                return Err(ParserError::UnexpectedToken {
-                    at: token.span,
-                    expected: "<identifier>",
-                }
-                .into());
+            TokenKind::EOF => Err(ParserError::UnexpectedEOF.into()),
+            _ => Err(ParserError::UnexpectedToken {
+                at: token.span,
+                expected: "<identifier>",
             }
+            .into()),
         }
     }
 
-    fn parse_list_section(
+    pub(crate) fn parse_list_section(
         input: &mut Peekable<impl Iterator<Item = Token>>,
     ) -> miette::Result<Vec<Token>> {
         let mut final_states = vec![];
 
+        // An empty list runs straight into the next section
+        if input
+            .peek()
+            .is_some_and(|token| token.kind == TokenKind::LeftSquareBracket)
+        {
+            return Ok(final_states);
+        }
+
         while let Some(token) = input.next() {
             match token.kind {
                 TokenKind::Identifier => {
@@ -511,12 +656,163 @@ impl Parser {
         panic!("reached end of iter without consuming EOF");
     }
 
+    /// Test cases are line based, so unlike the other sections this one looks at the
+    /// source between tokens to know where each line ends
+    fn parse_tests(
+        src: &str,
+        input: &mut Peekable<impl Iterator<Item = Token>>,
+    ) -> miette::Result<Vec<TestCase>> {
+        let mut tests = vec![];
+        let same_line = |end: usize, token: &Token| !src[end..token.span.offset()].contains('\n');
+
+        loop {
+            let first = input.peek().unwrap().clone();
+            match first.kind {
+                // Next section
+                TokenKind::LeftSquareBracket | TokenKind::EOF => return Ok(tests),
+                _ => {}
+            }
+
+            // Everything before the arrow is the input, an empty input can be written as ε
+            // or left out entirely
+            let mut end = first.span.offset();
+            while let Some(token) = input.next_if(|token| {
+                !matches!(
+                    token.kind,
+                    TokenKind::Arrow | TokenKind::LeftSquareBracket | TokenKind::EOF
+                ) && (token.span.offset() == first.span.offset() || same_line(end, token))
+            }) {
+                end = token.span.offset() + token.span.len();
+            }
+
+            let test_input = match &src[first.span.offset()..end] {
+                "ε" => "",
+                test_input => test_input,
+            };
+
+            // Assert arrow
+            let arrow_token = input.next().unwrap();
+            match arrow_token.kind {
+                TokenKind::Arrow => {}
+                TokenKind::EOF => {
+                    return Err(ParserError::UnexpectedEOF.into());
+                }
+                _ => {
+                    return Err(ParserError::UnexpectedToken {
+                        at: arrow_token.span,
+                        expected: "=>",
+                    }
+                    .into());
+                }
+            };
+
+            let verdict_token = input.next().unwrap();
+            let expected = match (&verdict_token.kind, verdict_token.src(src)) {
+                (TokenKind::Identifier, "ACCEPT" | "ACCEPTED") => Expectation::Accept,
+                (TokenKind::Identifier, "REJECT" | "REJECTED") => Expectation::Reject,
+                (TokenKind::EOF, _) => {
+                    return Err(ParserError::UnexpectedEOF.into());
+                }
+                _ => {
+                    return Err(ParserError::UnexpectedToken {
+                        at: verdict_token.span,
+                        expected: "ACCEPT or REJECT",
+                    }
+                    .into());
+                }
+            };
+
+            // Anything left on the same line is the expected tape
+            end = verdict_token.span.offset() + verdict_token.span.len();
+            let tape_start = input.peek().unwrap().span.offset();
+            let mut tape = None;
+            while let Some(token) = input.next_if(|token| {
+                !matches!(token.kind, TokenKind::LeftSquareBracket | TokenKind::EOF)
+                    && same_line(end, token)
+            }) {
+                end = token.span.offset() + token.span.len();
+                tape = Some(src[tape_start..end].to_string());
+            }
+
+            tests.push(TestCase {
+                input: test_input.to_string(),
+                expected,
+                tape,
+                span: SourceSpan::new(first.span.offset().into(), end - first.span.offset()),
+            });
+        }
+    }
+
+    /// Symbols a stack operation puts on the stack, top first: either one identifier or
+    /// a bracketed list like `[B, C, A]`, which may be empty
+    fn parse_stack_string(
+        input: &mut Peekable<impl Iterator<Item = Token>>,
+    ) -> miette::Result<Vec<Token>> {
+        let token = input.next().unwrap();
+        match token.kind {
+            TokenKind::Identifier => return Ok(vec![token]),
+            TokenKind::LeftSquareBracket => {}
+            TokenKind::EOF => {
+                return Err(ParserError::UnexpectedEOF.into());
+            }
+            _ => {
+                return Err(ParserError::UnexpectedToken {
+                    at: token.span,
+                    expected: "<identifier> or [",
+                }
+                .into());
+            }
+        }
+
+        let mut symbols = vec![];
+        if input
+            .next_if(|token| token.kind == TokenKind::RightSquareBracket)
+            .is_some()
+        {
+            return Ok(symbols);
+        }
+
+        loop {
+            let token = input.next().unwrap();
+            match token.kind {
+                TokenKind::Identifier => symbols.push(token),
+                TokenKind::EOF => {
+                    return Err(ParserError::UnexpectedEOF.into());
+                }
+                _ => {
+                    return Err(ParserError::UnexpectedToken {
+                        at: token.span,
+                        expected: "<identifier>",
+                    }
+                    .into());
+                }
+            }
+
+            // Assert comma or ]
+            let token = input.next().unwrap();
+            match token.kind {
+                TokenKind::Comma => {}
+                TokenKind::RightSquareBracket => return Ok(symbols),
+                TokenKind::EOF => {
+                    return Err(ParserError::UnexpectedEOF.into());
+                }
+                _ => {
+                    return Err(ParserError::UnexpectedToken {
+                        at: token.span,
+                        expected: ", or ]",
+                    }
+                    .into());
+                }
+            }
+        }
+    }
+
     fn parse_transitions(
         input: &mut Peekable<impl Iterator<Item = Token>>,
     ) -> miette::Result<Vec<TransitionInfo>> {
         let mut transitions = vec![];
 
-        while let Some(token) = input.next() {
+        while let Some(token) = input.next_if(|token| token.kind != TokenKind::LeftSquareBracket) {
             match token.kind {
                 TokenKind::Identifier => {
                     // Assert left paren
@@ -649,7 +945,7 @@ impl Parser {
                         input.next().unwrap();
                         let op_token = input.next().unwrap();
                         match op_token.kind {
-                            TokenKind::Push => {
+                            TokenKind::Push | TokenKind::Replace => {
                                 // Assert :
                                 let colon_token = input.next().unwrap();
                                 match colon_token.kind {
@@ -666,26 +962,12 @@ impl Parser {
                                     }
                                 };
 
-                                // We need our identifier now
-                                let stack_next_state_iden = input.next().unwrap();
-                                match stack_next_state_iden.kind {
-                                    TokenKind::Identifier => {
-                                        stack_next_state_token = Some(StackTransition::Push(
-                                            (), // Was op_token
-                                            stack_next_state_iden,
-                                        ));
-                                    }
-                                    TokenKind::EOF => {
-                                        return Err(ParserError::UnexpectedEOF.into());
-                                    }
-                                    _ => {
-                                        return Err(ParserError::UnexpectedToken {
-                                            at: stack_next_state_iden.span,
-                                            expected: "<identifier>",
-                                        }
-                                        .into());
-                                    }
-                                }
+                                // We need the symbols now
+                                let symbols = Parser::parse_stack_string(input)?;
+                                stack_next_state_token = Some(match op_token.kind {
+                                    TokenKind::Push => StackTransition::Push((), symbols),
+                                    _ => StackTransition::Replace((), symbols),
+                                });
                             }
                             TokenKind::Pop => {
                                 stack_next_state_token = Some(StackTransition::Pop(()));
@@ -743,7 +1025,7 @@ impl Parser {
                             _ => {
                                 return Err(ParserError::UnexpectedToken {
                                     at: op_token.span,
-                                    expected: "PUSH, POP, NOOP, WRITE, LEFT, or RIGHT",
+                                    expected: "PUSH, REPLACE, POP, NOOP, WRITE, LEFT, or RIGHT",
                                 }
                                 .into());
                             }
@@ -813,7 +1095,101 @@ impl Parser {
             }
         }
 
+        // Next section
+        if input.peek().is_some() {
+            return Ok(transitions);
+        }
+
         println!("{:?}", transitions);
         panic!("reached end of iter without consuming EOF");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_file(src: &str) -> miette::Result<Vec<TestCase>> {
+        Parser::lex(src).and_then(|tokens| Parser::parse_test_file(src, tokens))
+    }
+
+    /// Span of the token a parse of `src` failed at
+    fn error_at(src: &str) -> &str {
+        let error = test_file(src).unwrap_err();
+        let Some(ParserError::UnexpectedToken { at, .. }) = error.downcast_ref() else {
+            panic!("unexpected error {error:?}");
+        };
+        &src[at.offset()..at.offset() + at.len()]
+    }
+
+    #[test]
+    fn reads_test_cases_line_by_line() {
+        let src = "
+# Comments are skipped
+ε => ACCEPT
+ => REJECTED
+ab => REJECT
+11+1 => ACCEPT 111
+";
+        let tests = test_file(src).unwrap();
+
+        let cases: Vec<(&str, Expectation, Option<&str>)> = tests
+            .iter()
+            .map(|case| (case.input.as_str(), case.expected, case.tape.as_deref()))
+            .collect();
+        assert_eq!(
+            cases,
+            [
+                ("", Expectation::Accept, None),
+                ("", Expectation::Reject, None),
+                ("ab", Expectation::Reject, None),
+                ("11+1", Expectation::Accept, Some("111")),
+            ]
+        );
+
+        let last = &tests[3];
+        assert_eq!(
+            &src[last.span.offset()..last.span.offset() + last.span.len()],
+            "11+1 => ACCEPT 111"
+        );
+    }
+
+    #[test]
+    fn reads_the_tests_section_of_a_machine() {
+        let src = "
+[states]
+q0
+[alphabet]
+a
+[initial]
+q0
+[final]
+q0
+[transitions]
+q0(a) => (q0)
+[tests]
+aa => ACCEPT
+";
+        let machine = Parser::lex(src)
+            .and_then(|tokens| Parser::parse(src, tokens))
+            .unwrap();
+
+        let tests = machine.tests.unwrap();
+        assert_eq!(tests.len(), 1);
+        assert_eq!(tests[0].input, "aa");
+        assert_eq!(tests[0].expected, Expectation::Accept);
+    }
+
+    #[test]
+    fn points_at_malformed_test_lines() {
+        assert_eq!(error_at("ab => MAYBE\n"), "MAYBE");
+        // Without an arrow, the input runs to the end of the line
+        assert_eq!(error_at("ab ACCEPT\ncd => REJECT\n"), "cd");
+
+        let error = test_file("ab =>").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(ParserError::UnexpectedEOF)
+        ));
+    }
+}